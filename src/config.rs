@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::ErrorKind;
+use chrono::NaiveTime;
+use rnotifylib::message::Level;
+use serde::Deserialize;
+use crate::consumption::MeterConfig;
+use crate::daily::DailyDigestConfig;
+use crate::discovery::DiscoveryConfig;
+use crate::mqtt::MqttConfig;
+use crate::profile::ProfileConfig;
+use crate::quiet::QuietHoursConfig;
+use crate::region::RegionConfig;
+use crate::reward::RewardConfig;
+use crate::reminder::ReminderConfig;
+use crate::resource::matcher::ResourceMatchers;
+use crate::resource::schema::HeaderAliases;
+use crate::supplier::SupplierConfig;
+use crate::template::MessageTemplate;
+use crate::{NewPossibleEvent, POLL_INTERVAL};
+use crate::webhook::WebhookConfig;
+
+const CONFIG_FILE: &str = "dfs_alert.toml";
+
+/// Settings for dfs_alert itself, separate from the rnotify `routing.toml`.
+/// Every section is optional so that a missing file gives the old behaviour.
+#[derive(Deserialize, Debug)]
+pub struct DfsAlertConfig {
+    #[serde(default)]
+    #[serde(rename = "webhook")]
+    webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    mqtt: Option<MqttConfig>,
+    #[serde(default)]
+    reminders: Option<ReminderConfig>,
+    #[serde(default)]
+    levels: LevelConfig,
+    /// Keyed by event type, e.g. "published".
+    #[serde(default)]
+    templates: HashMap<String, MessageTemplate>,
+    #[serde(default)]
+    csv_aliases: HeaderAliases,
+    #[serde(default)]
+    #[serde(rename = "resources")]
+    resource_matchers: ResourceMatchers,
+    #[serde(default)]
+    discovery: Option<DiscoveryConfig>,
+    /// Whether to send a digest when any resource in a watched datapackage is modified.
+    #[serde(default)]
+    watch_all_resources: bool,
+    /// How long after ESO said they would follow up on an anticipated event to say there isn't one.
+    #[serde(default = "default_no_event_grace_minutes")]
+    no_event_grace_minutes: i64,
+    /// When ESO are expected to follow up on anticipated notices that don't give a time, e.g. "14:30:00".
+    #[serde(default = "default_follow_up")]
+    default_follow_up: NaiveTime,
+    /// Only requirements for this region are alerted about, if set.
+    #[serde(default)]
+    region: Option<RegionConfig>,
+    /// Households that get their own event messages. If there are none, everything goes to `routing.toml`.
+    #[serde(default)]
+    #[serde(rename = "profile")]
+    profiles: Vec<ProfileConfig>,
+    #[serde(default)]
+    #[serde(rename = "supplier")]
+    suppliers: Vec<SupplierConfig>,
+    /// Used to estimate the household's reward, if set.
+    #[serde(default)]
+    reward: Option<RewardConfig>,
+    /// Where to find smart meter readings, to work out the turn-down after each event.
+    #[serde(default)]
+    meter: Option<MeterConfig>,
+    /// When to hold back messages sent through `routing.toml`, until the quiet hours end.
+    #[serde(default)]
+    quiet_hours: Vec<QuietHoursConfig>,
+    /// A summary sent once a day, if set.
+    #[serde(default)]
+    daily_digest: Option<DailyDigestConfig>,
+    /// Whether to send a message saying what is being watched when dfs_alert starts.
+    #[serde(default)]
+    startup_message: bool,
+    /// Whether to send a message when dfs_alert is asked to stop.
+    #[serde(default)]
+    shutdown_message: bool,
+    /// How often to poll each datapackage, keyed by package name. Others are polled every 10 minutes.
+    #[serde(default)]
+    poll_minutes: HashMap<String, u64>,
+    /// The least time between requests to the ESO data portal, across every feed.
+    #[serde(default = "default_request_interval_millis")]
+    request_interval_millis: u64,
+}
+
+impl DfsAlertConfig {
+    pub fn get_webhooks(&self) -> &Vec<WebhookConfig> {
+        &self.webhooks
+    }
+
+    pub fn get_mqtt(&self) -> &Option<MqttConfig> {
+        &self.mqtt
+    }
+
+    pub fn get_reminders(&self) -> &Option<ReminderConfig> {
+        &self.reminders
+    }
+
+    pub fn get_levels(&self) -> &LevelConfig {
+        &self.levels
+    }
+
+    pub fn get_templates(&self) -> &HashMap<String, MessageTemplate> {
+        &self.templates
+    }
+
+    pub fn get_csv_aliases(&self) -> &HeaderAliases {
+        &self.csv_aliases
+    }
+
+    pub fn get_resource_matchers(&self) -> &ResourceMatchers {
+        &self.resource_matchers
+    }
+
+    pub fn get_discovery(&self) -> &Option<DiscoveryConfig> {
+        &self.discovery
+    }
+
+    pub fn is_watch_all_resources(&self) -> bool {
+        self.watch_all_resources
+    }
+
+    pub fn get_region(&self) -> &Option<RegionConfig> {
+        &self.region
+    }
+
+    pub fn get_profiles(&self) -> &Vec<ProfileConfig> {
+        &self.profiles
+    }
+
+    pub fn get_suppliers(&self) -> &Vec<SupplierConfig> {
+        &self.suppliers
+    }
+
+    pub fn get_reward(&self) -> &Option<RewardConfig> {
+        &self.reward
+    }
+
+    pub fn get_meter(&self) -> &Option<MeterConfig> {
+        &self.meter
+    }
+
+    pub fn get_quiet_hours(&self) -> &Vec<QuietHoursConfig> {
+        &self.quiet_hours
+    }
+
+    pub fn get_daily_digest(&self) -> &Option<DailyDigestConfig> {
+        &self.daily_digest
+    }
+
+    pub fn is_startup_message(&self) -> bool {
+        self.startup_message
+    }
+
+    pub fn is_shutdown_message(&self) -> bool {
+        self.shutdown_message
+    }
+
+    pub fn get_poll_interval(&self, package: &str) -> chrono::Duration {
+        match self.poll_minutes.get(package) {
+            Some(minutes) => chrono::Duration::minutes(*minutes as i64),
+            None => chrono::Duration::from_std(POLL_INTERVAL).unwrap(),
+        }
+    }
+
+    pub fn get_request_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.request_interval_millis)
+    }
+
+    pub fn get_no_event_grace(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.no_event_grace_minutes)
+    }
+
+    pub fn get_default_follow_up(&self) -> NaiveTime {
+        self.default_follow_up
+    }
+}
+
+fn default_no_event_grace_minutes() -> i64 {
+    30
+}
+
+fn default_follow_up() -> NaiveTime {
+    NaiveTime::from_hms_opt(14, 30, 0).unwrap()
+}
+
+fn default_request_interval_millis() -> u64 {
+    500
+}
+
+fn default_level() -> Level {
+    Level::Info
+}
+
+fn default_unrecognised_level() -> Level {
+    Level::Warn
+}
+
+/// The level of message sent for each type of event, configured with a `[levels]` table.
+#[derive(Deserialize, Debug, Clone)]
+pub struct LevelConfig {
+    #[serde(default = "default_level")]
+    anticipated: Level,
+    #[serde(default = "default_level")]
+    published: Level,
+    #[serde(default = "default_level")]
+    cancelled: Level,
+    #[serde(default = "default_level")]
+    test: Level,
+    #[serde(default = "default_level")]
+    supplier_confirmed: Level,
+    #[serde(default = "default_unrecognised_level")]
+    unrecognised: Level,
+}
+
+impl Default for LevelConfig {
+    fn default() -> Self {
+        Self {
+            anticipated: default_level(),
+            published: default_level(),
+            cancelled: default_level(),
+            test: default_level(),
+            supplier_confirmed: default_level(),
+            unrecognised: default_unrecognised_level(),
+        }
+    }
+}
+
+impl LevelConfig {
+    pub fn get_level(&self, event: &NewPossibleEvent) -> Level {
+        match event {
+            NewPossibleEvent::Expected(_) => self.anticipated.clone(),
+            NewPossibleEvent::Confirmed(_) => self.published.clone(),
+            NewPossibleEvent::Cancelled(_) => self.cancelled.clone(),
+            NewPossibleEvent::OurSupplierConfirmed => self.supplier_confirmed.clone(),
+            NewPossibleEvent::Test(_) => self.test.clone(),
+            NewPossibleEvent::Unrecognised(_) => self.unrecognised.clone(),
+        }
+    }
+}
+
+pub fn load_config() -> Result<DfsAlertConfig, Box<dyn Error>> {
+    let s = match std::fs::read_to_string(CONFIG_FILE) {
+        Ok(s) => s,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            println!("No {} found, using defaults.", CONFIG_FILE);
+            // Parsed rather than using Default, so that the serde defaults are used.
+            String::new()
+        }
+        Err(err) => return Err(err.into()),
+    };
+    Ok(toml::from_str(&s)?)
+}
@@ -0,0 +1,351 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::path::PathBuf;
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, Weekday};
+use rnotifylib::message::{Level, Message};
+use rnotifylib::message::builder::MessageBuilder;
+use rnotifylib::message::component::Component;
+use rnotifylib::message::detail_builder::FormattedStringAppendable;
+use reqwest::Client;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde::de::{Error as SerdeError, Unexpected};
+use crate::resource::anticipation::relative_day;
+use crate::resource::schema::{HeaderAliases, METER_READING_SCHEMA};
+
+/// How many days of the same kind (weekday or weekend) the baseline is averaged over.
+const WEEKDAY_REFERENCE_DAYS: usize = 10;
+const WEEKEND_REFERENCE_DAYS: usize = 4;
+/// How far back to look for reference days.
+pub const MAX_LOOKBACK_DAYS: i64 = 60;
+/// The in-day adjustment compares the hours before the event, from 4 hours to 1 hour before it starts.
+const ADJUSTMENT_FROM_HOURS: i64 = 4;
+const ADJUSTMENT_TO_HOURS: i64 = 1;
+
+/// Half-hourly consumption in kWh, keyed by the start of each half hour in local time.
+pub type Readings = BTreeMap<NaiveDateTime, f64>;
+
+fn default_octopus_url() -> String {
+    "https://api.octopus.energy".to_owned()
+}
+
+/// Where to get the household's consumption from, configured with a `[meter]` table in `dfs_alert.toml`.
+/// Either `directory` or a `[meter.octopus]` table should be given.
+#[derive(Deserialize, Debug, Clone)]
+pub struct MeterConfig {
+    #[serde(default)]
+    directory: Option<String>,
+    #[serde(default)]
+    octopus: Option<OctopusConfig>,
+}
+
+/// An electricity meter on the Octopus consumption API, or anything serving the same shape.
+#[derive(Deserialize, Debug, Clone)]
+pub struct OctopusConfig {
+    #[serde(default = "default_octopus_url")]
+    url: String,
+    api_key: String,
+    mpan: String,
+    serial: String,
+}
+
+impl MeterConfig {
+    pub fn create_source(&self, aliases: &HeaderAliases) -> Result<Box<dyn ConsumptionSource>, Box<dyn Error>> {
+        match (&self.octopus, &self.directory) {
+            (Some(octopus), _) => Ok(Box::new(OctopusSource::new(octopus.clone()))),
+            (None, Some(directory)) => Ok(Box::new(FileSource::new(PathBuf::from(directory), aliases.clone()))),
+            (None, None) => Err("[meter] needs a directory or an [meter.octopus] table".into()),
+        }
+    }
+}
+
+/// Somewhere that half-hourly consumption can be fetched from.
+#[async_trait(?Send)]
+pub trait ConsumptionSource {
+    /// The readings between the two times, or as many of them as there are.
+    async fn fetch(&self, from: NaiveDateTime, to: NaiveDateTime) -> Result<Readings, Box<dyn Error>>;
+}
+
+fn local_date_time_from_str<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
+    where D: Deserializer<'de> {
+    let s = String::deserialize(deserializer)?;
+    DateTime::parse_from_rfc3339(&s)
+        .map(|time| time.with_timezone(&Local).naive_local())
+        .or_else(|_| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S"))
+        .or_else(|_| NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M:%S"))
+        .or_else(|_| NaiveDateTime::parse_from_str(&s, "%d/%m/%Y %H:%M"))
+        .map_err(|_| D::Error::invalid_value(Unexpected::Other(&s), &"an RFC 3339 date and time"))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MeterReading {
+    #[serde(rename = "Consumption (kWh)")]
+    #[serde(alias = "consumption")]
+    consumption: f64,
+    #[serde(rename = "Start")]
+    #[serde(alias = "interval_start")]
+    #[serde(deserialize_with = "local_date_time_from_str")]
+    start: NaiveDateTime,
+}
+
+/// Csv files in a directory, e.g. as exported from the supplier's website.
+pub struct FileSource {
+    directory: PathBuf,
+    aliases: HeaderAliases,
+}
+
+impl FileSource {
+    pub fn new(directory: PathBuf, aliases: HeaderAliases) -> Self {
+        Self {
+            directory,
+            aliases,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl ConsumptionSource for FileSource {
+    async fn fetch(&self, from: NaiveDateTime, to: NaiveDateTime) -> Result<Readings, Box<dyn Error>> {
+        let mut readings = Readings::new();
+        for entry in std::fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path.extension().filter(|extension| extension.eq_ignore_ascii_case("csv")).is_none() {
+                continue;
+            }
+            let (rows, drift) = METER_READING_SCHEMA.read::<MeterReading>(&std::fs::read_to_string(&path)?, &self.aliases)?
+                .into_parts();
+            if let Some(drift) = drift {
+                eprintln!("Meter readings in {} aren't in the expected format: {:?}", path.display(), drift);
+            }
+            readings.extend(rows.into_iter()
+                .filter(|row| row.start >= from && row.start < to)
+                .map(|row| (row.start, row.consumption)));
+        }
+        Ok(readings)
+    }
+}
+
+#[derive(Deserialize)]
+struct ConsumptionPage {
+    next: Option<String>,
+    results: Vec<MeterReading>,
+}
+
+/// The Octopus consumption API, which gives readings a page at a time.
+pub struct OctopusSource {
+    client: Client,
+    config: OctopusConfig,
+}
+
+impl OctopusSource {
+    pub fn new(config: OctopusConfig) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+        }
+    }
+}
+
+fn to_rfc3339(time: &NaiveDateTime) -> String {
+    time.and_local_timezone(Local).earliest()
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_else(|| time.format("%Y-%m-%dT%H:%M:%S").to_string())
+}
+
+#[async_trait(?Send)]
+impl ConsumptionSource for OctopusSource {
+    async fn fetch(&self, from: NaiveDateTime, to: NaiveDateTime) -> Result<Readings, Box<dyn Error>> {
+        let url = format!("{}/v1/electricity-meter-points/{}/meters/{}/consumption/",
+                          self.config.url.trim_end_matches('/'), self.config.mpan, self.config.serial);
+        let mut request = self.client.get(url)
+            .query(&[("period_from", to_rfc3339(&from)), ("period_to", to_rfc3339(&to)), ("page_size", "1500".to_owned())]);
+        let mut readings = Readings::new();
+        loop {
+            let page: ConsumptionPage = request
+                .basic_auth(&self.config.api_key, Option::<&str>::None)
+                .send().await?
+                .error_for_status()?
+                .json().await?;
+            readings.extend(page.results.into_iter().map(|row| (row.start, row.consumption)));
+            match page.next {
+                Some(next) => request = self.client.get(next),
+                None => break,
+            }
+        }
+        Ok(readings)
+    }
+}
+
+fn half_hours(start: NaiveDateTime, end: NaiveDateTime) -> Vec<NaiveDateTime> {
+    let mut slots = vec![];
+    let mut slot = start;
+    while slot < end {
+        slots.push(slot);
+        slot += Duration::minutes(30);
+    }
+    slots
+}
+
+fn is_weekend(date: &NaiveDate) -> bool {
+    matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+/// How much the household turned down during an event, compared to its baseline. Kept in saved state.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Turndown {
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    baseline_kwh: f64,
+    actual_kwh: f64,
+    reference_days: usize,
+}
+
+impl Turndown {
+    /// Works out the turn-down following the DFS baseline methodology:
+    /// the average of the same half hours over the previous 10 weekdays (or 4 weekend days), skipping other event days,
+    /// adjusted by how far the household was above or below that from 4 hours to 1 hour before the event.
+    /// Returns `None` if there aren't readings for the whole event and at least one reference day.
+    pub fn calculate(readings: &Readings, start: NaiveDateTime, end: NaiveDateTime, event_dates: &BTreeSet<NaiveDate>) -> Option<Self> {
+        let event_slots = half_hours(start, end);
+        let adjustment_slots = half_hours(start - Duration::hours(ADJUSTMENT_FROM_HOURS), start - Duration::hours(ADJUSTMENT_TO_HOURS));
+        let actual: Vec<f64> = event_slots.iter()
+            .map(|slot| readings.get(slot).copied())
+            .collect::<Option<_>>()?;
+
+        let date = start.date();
+        let wanted = if is_weekend(&date) { WEEKEND_REFERENCE_DAYS } else { WEEKDAY_REFERENCE_DAYS };
+        let reference_days: Vec<i64> = (1..=MAX_LOOKBACK_DAYS)
+            .filter(|days| {
+                let day = date - Duration::days(*days);
+                is_weekend(&day) == is_weekend(&date) && !event_dates.contains(&day)
+                    && event_slots.iter().chain(&adjustment_slots)
+                        .all(|slot| readings.contains_key(&(*slot - Duration::days(*days))))
+            })
+            .take(wanted)
+            .collect();
+        if reference_days.is_empty() {
+            return None;
+        }
+        let unadjusted = |slot: &NaiveDateTime| reference_days.iter()
+            .map(|days| readings[&(*slot - Duration::days(*days))])
+            .sum::<f64>() / reference_days.len() as f64;
+
+        // Without readings for the hours before the event, the baseline is left unadjusted.
+        let adjustment = adjustment_slots.iter()
+            .map(|slot| readings.get(slot).map(|reading| reading - unadjusted(slot)))
+            .collect::<Option<Vec<f64>>>()
+            .map(|differences| differences.iter().sum::<f64>() / differences.len() as f64)
+            .unwrap_or_default();
+        let baseline_kwh = event_slots.iter()
+            .map(|slot| (unadjusted(slot) + adjustment).max(0.0))
+            .sum();
+
+        Some(Self {
+            start,
+            end,
+            baseline_kwh,
+            actual_kwh: actual.iter().sum(),
+            reference_days: reference_days.len(),
+        })
+    }
+
+    pub fn get_start(&self) -> &NaiveDateTime {
+        &self.start
+    }
+
+    pub fn get_saved_kwh(&self) -> f64 {
+        self.baseline_kwh - self.actual_kwh
+    }
+
+    pub fn create_message(&self, feed: &str) -> Message {
+        let today = Local::now().date_naive();
+        let summary = format!("Saved {:.2} kWh during the DFS event {}", self.get_saved_kwh(), relative_day(&self.start.date(), &today));
+        let window = format!("{} - {}", self.start.format("%H:%M"), self.end.format("%H:%M"));
+        let mut message_builder = MessageBuilder::new();
+        message_builder
+            .level(Level::Info)
+            .title(format!("DFS event turn-down - {}", feed))
+            .body(|body| {
+                body.raw(format!("{} ({}) - {}", summary, window, feed));
+                body.text_block(|block| {
+                    block.append_plain(format!("{}\n", summary));
+                    block.append_plain(format!("Event window: {}\n", window));
+                });
+                body.section("Details", |builder| {
+                    builder.append_plain(format!("Baseline: {:.2} kWh, from {} reference days\n", self.baseline_kwh, self.reference_days));
+                    builder.append_plain(format!("Actual: {:.2} kWh\n", self.actual_kwh));
+                });
+            })
+            .component(Component::from("dfs/turndown"))
+            .author("dfs_alert");
+        message_builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use chrono::{Duration, NaiveDate};
+    use crate::consumption::{ConsumptionSource, half_hours, OctopusConfig, OctopusSource, Readings, Turndown};
+
+    #[tokio::test]
+    async fn test_octopus_source() {
+        // A local stand-in for the consumption API, which serves one page.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let read = stream.read(&mut request).unwrap();
+            let body = r#"{"count":2,"next":null,"previous":null,"results":[
+                {"consumption":0.25,"interval_start":"2023-01-25T17:00:00","interval_end":"2023-01-25T17:30:00"},
+                {"consumption":0.5,"interval_start":"2023-01-25T17:30:00","interval_end":"2023-01-25T18:00:00"}]}"#;
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+            String::from_utf8_lossy(&request[..read]).into_owned()
+        });
+
+        let source = OctopusSource::new(OctopusConfig {
+            url,
+            api_key: "key".to_owned(),
+            mpan: "123".to_owned(),
+            serial: "ABC".to_owned(),
+        });
+        let start = NaiveDate::from_ymd_opt(2023, 1, 25).unwrap().and_hms_opt(17, 0, 0).unwrap();
+        let readings = source.fetch(start, start + Duration::hours(1)).await.expect("Should fetch");
+        assert_eq!(readings.get(&start), Some(&0.25));
+        assert_eq!(readings.get(&(start + Duration::minutes(30))), Some(&0.5));
+        let request = server.join().unwrap();
+        assert!(request.starts_with("GET /v1/electricity-meter-points/123/meters/ABC/consumption/?period_from="), "{}", request);
+    }
+
+    #[test]
+    fn test_turndown() {
+        // Wednesday.
+        let date = NaiveDate::from_ymd_opt(2023, 1, 25).unwrap();
+        let start = date.and_hms_opt(17, 0, 0).unwrap();
+        let end = start + Duration::hours(1);
+        let mut readings = Readings::new();
+        for slot in half_hours(date.and_hms_opt(0, 0, 0).unwrap() - Duration::days(20), end) {
+            readings.insert(slot, 1.0);
+        }
+        // The day before had an event, so shouldn't count.
+        let previous_event = date - Duration::days(1);
+        readings.insert(previous_event.and_hms_opt(17, 0, 0).unwrap(), 0.0);
+        // Using more in the afternoon adjusts the baseline up.
+        for slot in half_hours(start - Duration::hours(4), start - Duration::hours(1)) {
+            readings.insert(slot, 1.5);
+        }
+        readings.insert(start, 0.25);
+        readings.insert(start + Duration::minutes(30), 0.25);
+
+        let event_dates = BTreeSet::from([previous_event, date]);
+        let turndown = Turndown::calculate(&readings, start, end, &event_dates).expect("Should have readings");
+        assert_eq!(turndown.reference_days, 10);
+        assert_eq!(turndown.baseline_kwh, 3.0);
+        assert_eq!(turndown.get_saved_kwh(), 2.5);
+
+        assert_eq!(Turndown::calculate(&readings, start, end + Duration::minutes(30), &event_dates), None);
+    }
+}
@@ -0,0 +1,201 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use rnotifylib::message::{Level, Message};
+use rnotifylib::message::builder::MessageBuilder;
+use rnotifylib::message::component::Component;
+use rnotifylib::message::detail_builder::FormattedStringAppendable;
+use serde::Deserialize;
+use crate::feed::Feed;
+use crate::lifecycle::EventStage;
+use crate::region::Region;
+
+fn default_time() -> NaiveTime {
+    NaiveTime::from_hms_opt(8, 0, 0).unwrap()
+}
+
+/// A once a day summary, configured with a `[daily_digest]` table.
+/// It is sent on the first poll after `time`, so may be up to a poll interval late.
+#[derive(Deserialize, Debug, Clone)]
+pub struct DailyDigestConfig {
+    #[serde(default = "default_time")]
+    time: NaiveTime,
+}
+
+impl DailyDigestConfig {
+    /// Whether it is time to send today's digest, given the date the last one was sent.
+    pub fn is_due(&self, last_sent: &Option<NaiveDate>, now: &NaiveDateTime) -> bool {
+        now.time() >= self.time && last_sent.as_ref() != Some(&now.date())
+    }
+}
+
+#[derive(Debug)]
+struct DaySummary {
+    date: NaiveDate,
+    stage: Option<EventStage>,
+    window: Option<(NaiveDateTime, NaiveDateTime)>,
+}
+
+impl DaySummary {
+    fn describe(&self) -> String {
+        let stage = match self.stage {
+            Some(stage) => stage.to_string(),
+            None => "no event".to_owned(),
+        };
+        match self.window {
+            Some((start, end)) => format!("{}, {} - {}", stage, start.format("%H:%M"), end.format("%H:%M")),
+            None => stage,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FeedSummary {
+    name: String,
+    test: bool,
+    today: DaySummary,
+    tomorrow: DaySummary,
+    /// Windows that ended in the last day.
+    recent: Vec<(NaiveDateTime, NaiveDateTime)>,
+    /// How the supplier did in recent events.
+    participation: Vec<String>,
+    health: String,
+}
+
+impl FeedSummary {
+    fn title(&self) -> String {
+        if self.test { format!("{} (test)", self.name) } else { self.name.clone() }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Today ({}): {}\n", self.today.date.format("%d/%m"), self.today.describe()),
+            format!("Tomorrow ({}): {}\n", self.tomorrow.date.format("%d/%m"), self.tomorrow.describe()),
+        ];
+        for (start, end) in &self.recent {
+            lines.push(format!("Event in the last 24 hours: {} - {}\n", start.format("%d/%m %H:%M"), end.format("%H:%M")));
+        }
+        for participation in &self.participation {
+            lines.push(format!("{}\n", participation));
+        }
+        lines.push(format!("Health: {}\n", self.health));
+        lines
+    }
+}
+
+/// What every feed looks like today, tomorrow and over the last day.
+#[derive(Debug)]
+pub struct DailyDigest {
+    date: NaiveDate,
+    feeds: Vec<FeedSummary>,
+}
+
+impl DailyDigest {
+    /// Only the event windows in the region are included.
+    pub fn new(feeds: &mut [Feed], region: &Option<Region>, now: &NaiveDateTime) -> Self {
+        let today = now.date();
+        let summaries = feeds.iter_mut()
+            .map(|feed| {
+                let name = feed.get_name().to_owned();
+                let test = feed.is_test();
+                let health = feed.get_health().describe();
+                let resources = feed.get_resources_mut();
+                let requirements = resources.get_requirements().in_region(region);
+                let mut day = |date: NaiveDate| DaySummary {
+                    date,
+                    stage: resources.get_lifecycle().get_stage(&date),
+                    window: requirements.get_window(&date),
+                };
+                let (today_summary, tomorrow) = (day(today), day(today + Duration::days(1)));
+                let recent = requirements.get_windows().into_iter()
+                    .filter(|(_, end)| end <= now && *now - *end < Duration::days(1))
+                    .collect();
+                let participation = resources.get_history().iter()
+                    .filter(|outcome| outcome.get_date() >= &(today - Duration::days(1)))
+                    .map(|outcome| outcome.describe(&today))
+                    .collect();
+                FeedSummary {
+                    name,
+                    test,
+                    today: today_summary,
+                    tomorrow,
+                    recent,
+                    participation,
+                    health,
+                }
+            })
+            .collect();
+        Self {
+            date: today,
+            feeds: summaries,
+        }
+    }
+
+    pub fn create_message(&self) -> Message {
+        let events = self.feeds.iter().filter(|feed| !feed.test && feed.today.window.is_some()).count();
+        let headline = match events {
+            0 => "No live DFS events today\n".to_owned(),
+            n => format!("{} live feeds have a DFS event today\n", n),
+        };
+        let sections: Vec<(String, Vec<String>)> = self.feeds.iter()
+            .map(|feed| (feed.title(), feed.lines()))
+            .collect();
+        let raw: String = sections.iter()
+            .map(|(title, lines)| format!("{}:\n{}", title, lines.concat()))
+            .collect();
+        let mut message_builder = MessageBuilder::new();
+        message_builder
+            .level(Level::Info)
+            .title(format!("DFS daily digest - {}", self.date.format("%d/%m/%Y")))
+            .body(|body| {
+                body.raw(format!("{}{}", headline, raw));
+                body.text_block(|block| {
+                    block.append_plain(headline);
+                });
+                for (title, lines) in &sections {
+                    body.section(title, |builder| {
+                        for line in lines {
+                            builder.append_plain(line);
+                        }
+                    });
+                }
+            })
+            .component(Component::from("dfs_alert/daily_digest"))
+            .author("dfs_alert");
+        message_builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use crate::daily::{DailyDigestConfig, DaySummary, FeedSummary};
+
+    #[test]
+    fn test_due_once_a_day() {
+        let config: DailyDigestConfig = toml::from_str("").unwrap();
+        let date = NaiveDate::from_ymd_opt(2023, 12, 15).unwrap();
+        let early = date.and_hms_opt(7, 50, 0).unwrap();
+        let later = date.and_hms_opt(8, 10, 0).unwrap();
+        assert!(!config.is_due(&None, &early));
+        assert!(config.is_due(&None, &later));
+        assert!(config.is_due(&date.pred_opt(), &later));
+        assert!(!config.is_due(&Some(date), &later));
+    }
+
+    #[test]
+    fn test_feed_lines() {
+        let date = NaiveDate::from_ymd_opt(2023, 12, 15).unwrap();
+        let window = (date.and_hms_opt(17, 0, 0).unwrap(), date.and_hms_opt(18, 0, 0).unwrap());
+        let feed = FeedSummary {
+            name: "live".to_owned(),
+            test: true,
+            today: DaySummary { date, stage: None, window: Some(window) },
+            tomorrow: DaySummary { date: date.succ_opt().unwrap(), stage: None, window: None },
+            recent: vec![],
+            participation: vec![],
+            health: "all polls succeeded".to_owned(),
+        };
+        assert_eq!(feed.title(), "live (test)");
+        assert_eq!(feed.lines(), vec!["Today (15/12): no event, 17:00 - 18:00\n", "Tomorrow (16/12): no event\n",
+                                      "Health: all polls succeeded\n"]);
+    }
+}
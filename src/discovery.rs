@@ -0,0 +1,209 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use chrono::{Duration, NaiveDateTime};
+use reqwest::Client;
+use rnotifylib::message::{Level, Message};
+use rnotifylib::message::builder::MessageBuilder;
+use rnotifylib::message::component::Component;
+use rnotifylib::message::detail_builder::FormattedStringAppendable;
+use serde::Deserialize;
+use crate::feed::is_test_package;
+
+const PACKAGE_SEARCH_URL: &str = "https://api.nationalgrideso.com/api/3/action/package_search";
+const PACKAGE_PAGE_URL: &str = "https://www.nationalgrideso.com/data-portal/";
+
+fn default_query() -> String {
+    "demand flexibility service".to_owned()
+}
+
+fn default_name_filter() -> String {
+    "demand-flexibility".to_owned()
+}
+
+fn default_interval_hours() -> i64 {
+    24
+}
+
+/// Configured with a `[discovery]` table in `dfs_alert.toml`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct DiscoveryConfig {
+    #[serde(default = "default_query")]
+    query: String,
+    /// Only packages with this in their name count, as the search is quite loose.
+    #[serde(default = "default_name_filter")]
+    name_filter: String,
+    #[serde(default = "default_interval_hours")]
+    interval_hours: i64,
+    /// Whether to start watching new packages straight away.
+    #[serde(default)]
+    auto_watch: bool,
+}
+
+impl DiscoveryConfig {
+    pub fn is_auto_watch(&self) -> bool {
+        self.auto_watch
+    }
+}
+
+#[derive(Deserialize)]
+struct PackageSearchResult {
+    success: bool,
+    result: PackageSearchResults,
+}
+
+#[derive(Deserialize)]
+struct PackageSearchResults {
+    results: Vec<Package>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Package {
+    name: String,
+    #[serde(default)]
+    title: String,
+}
+
+impl Package {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_test(&self) -> bool {
+        is_test_package(&self.name)
+    }
+
+    pub fn create_message(&self, watching: bool) -> Message {
+        let status = if watching { "now being watched" } else { "not being watched" };
+        let mut message_builder = MessageBuilder::new();
+        message_builder
+            .level(Level::Info)
+            .title(format!("New DFS datapackage - {}", self.title))
+            .body(|body| {
+                body.raw(format!("New DFS datapackage '{}' ({}), {}", self.title, self.name, status));
+                body.text_block(|block| {
+                    block.append_plain(format!("A new DFS datapackage '{}' ({}) has been published.\n", self.title, self.name));
+                    if watching {
+                        block.append_plain("dfs_alert has started watching it.\n");
+                    }
+                    else {
+                        block.append_plain("It is not being watched, add it to the config or turn on auto_watch.\n");
+                    }
+                });
+                body.section("Links", |builder| {
+                    builder.append_plain(format!("{}{}\n", PACKAGE_PAGE_URL, self.name));
+                });
+            })
+            .component(Component::from("dfs_alert/new_package"))
+            .author("dfs_alert");
+        message_builder.build()
+    }
+}
+
+/// Periodically searches CKAN for DFS datapackages that aren't known about yet.
+pub struct Discovery {
+    config: DiscoveryConfig,
+    client: Client,
+    known_packages: BTreeSet<String>,
+    last_run: Option<NaiveDateTime>,
+}
+
+impl Discovery {
+    pub fn new(config: DiscoveryConfig, known_packages: BTreeSet<String>) -> Self {
+        Self {
+            config,
+            client: Client::new(),
+            known_packages,
+            last_run: None,
+        }
+    }
+
+    pub fn get_config(&self) -> &DiscoveryConfig {
+        &self.config
+    }
+
+    pub fn get_known_packages(&self) -> &BTreeSet<String> {
+        &self.known_packages
+    }
+
+    pub fn is_due(&self, now: &NaiveDateTime) -> bool {
+        self.last_run
+            .filter(|last| *now - *last < Duration::hours(self.config.interval_hours))
+            .is_none()
+    }
+
+    /// Searches for packages, returning the ones that are new and not already watched.
+    /// The first search only records what exists, so that old packages aren't reported.
+    pub async fn run(&mut self, now: &NaiveDateTime, watched: &[&str]) -> Result<Vec<Package>, Box<dyn Error>> {
+        self.last_run = Some(*now);
+        let result: PackageSearchResult = self.client.get(PACKAGE_SEARCH_URL)
+            .query(&[("q", self.config.query.as_str()), ("rows", "100")])
+            .send().await?
+            .json().await?;
+        if !result.success {
+            return Err("Package search not a success!".into());
+        }
+
+        Ok(self.record(result.result.results, watched))
+    }
+
+    /// Remembers the packages that pass the name filter, returning the ones that are new and not already watched.
+    /// Nothing is returned if no packages were known before.
+    fn record(&mut self, found: Vec<Package>, watched: &[&str]) -> Vec<Package> {
+        let found: Vec<Package> = found.into_iter()
+            .filter(|package| package.name.contains(&self.config.name_filter))
+            .collect();
+        let first_run = self.known_packages.is_empty();
+        let mut new = vec![];
+        for package in found {
+            if self.known_packages.insert(package.name.clone()) && !first_run
+                && !watched.contains(&package.name.as_str()) {
+                new.push(package);
+            }
+        }
+        new
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+    use crate::discovery::{Discovery, DiscoveryConfig, Package};
+    use crate::feed::is_test_package;
+
+    fn package(name: &str) -> Package {
+        Package {
+            name: name.to_owned(),
+            title: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let config: DiscoveryConfig = toml::from_str("").unwrap();
+        let mut discovery = Discovery::new(config, BTreeSet::new());
+
+        // The first search is only a baseline.
+        let new = discovery.record(vec![package("demand-flexibility-service"), package("unrelated")], &[]);
+        assert!(new.is_empty());
+        assert_eq!(discovery.get_known_packages().iter().collect::<Vec<_>>(), vec!["demand-flexibility-service"]);
+
+        let new = discovery.record(vec![
+            package("demand-flexibility-service"),
+            package("demand-flexibility-service-2024"),
+            package("demand-flexibility-watched"),
+            package("something-else"),
+        ], &["demand-flexibility-watched"]);
+        let names: Vec<&str> = new.iter().map(Package::get_name).collect();
+        assert_eq!(names, vec!["demand-flexibility-service-2024"]);
+        assert!(discovery.get_known_packages().contains("demand-flexibility-watched"));
+        assert!(!discovery.get_known_packages().contains("something-else"));
+    }
+
+    #[test]
+    fn test_is_test_package() {
+        assert!(is_test_package("demand-flexibility-service-test-events"));
+        assert!(is_test_package("dfs_test"));
+        assert!(!is_test_package("demand-flexibility-service-live-events"));
+        assert!(!is_test_package("demand-flexibility-contest"));
+    }
+}
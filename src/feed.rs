@@ -0,0 +1,121 @@
+use chrono::NaiveDateTime;
+use crate::resource::anticipation::IndustryNotificationResource;
+use crate::resource::PreviousResources;
+
+pub const DATAPACKAGE_SHOW_URL: &str = "https://api.nationalgrideso.com/api/3/action/datapackage_show?id=";
+
+pub const LIVE_PACKAGE_2022: &str = "demand-flexibility-service-live-events";
+pub const TEST_PACKAGE_2022: &str = "demand-flexibility-service-test-events";
+pub const NEW_PACKAGE_2023: &str = "demand-flexibility-service";
+
+/// A guess at whether a datapackage is only for test events, based on its name, e.g. "demand-flexibility-service-test-events".
+pub fn is_test_package(name: &str) -> bool {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|part| part.eq_ignore_ascii_case("test"))
+}
+
+/// A CKAN datapackage that is checked for DFS notifications.
+pub struct Feed {
+    name: String,
+    package: String,
+    test: bool,
+    resources: PreviousResources,
+    health: PollHealth,
+    /// `None` until it has been polled once.
+    next_poll: Option<NaiveDateTime>,
+}
+
+impl Feed {
+    pub fn new(name: &str, package: &str, test: bool) -> Self {
+        Self {
+            name: name.to_owned(),
+            package: package.to_owned(),
+            test,
+            resources: PreviousResources::create(IndustryNotificationResource::default()),
+            health: PollHealth::default(),
+            next_poll: None,
+        }
+    }
+
+    /// The feeds that were watched before packages could be discovered.
+    pub fn built_in() -> Vec<Self> {
+        vec![
+            Feed::new("live (old)", LIVE_PACKAGE_2022, false),
+            Feed::new("test (old)", TEST_PACKAGE_2022, true),
+            Feed::new("new always live", NEW_PACKAGE_2023, false),
+        ]
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_package(&self) -> &str {
+        &self.package
+    }
+
+    pub fn get_url(&self) -> String {
+        format!("{}{}", DATAPACKAGE_SHOW_URL, self.package)
+    }
+
+    /// Whether everything on this feed is a test.
+    pub fn is_test(&self) -> bool {
+        self.test
+    }
+
+    pub fn get_resources(&self) -> &PreviousResources {
+        &self.resources
+    }
+
+    pub fn get_resources_mut(&mut self) -> &mut PreviousResources {
+        &mut self.resources
+    }
+
+    /// Whether it's time to poll the feed again.
+    pub fn is_due(&self, now: &NaiveDateTime) -> bool {
+        self.next_poll.map(|next_poll| &next_poll <= now).unwrap_or(true)
+    }
+
+    pub fn get_next_poll(&self) -> Option<NaiveDateTime> {
+        self.next_poll
+    }
+
+    pub fn schedule_next(&mut self, next_poll: NaiveDateTime) {
+        self.next_poll = Some(next_poll);
+    }
+
+    /// Counts a poll of the feed, and the error if it failed.
+    pub fn record_poll(&mut self, error: Option<String>) {
+        self.health.polls += 1;
+        if error.is_some() {
+            self.health.failures += 1;
+            self.health.last_error = error;
+        }
+    }
+
+    pub fn get_health(&self) -> &PollHealth {
+        &self.health
+    }
+
+    /// Starts counting polls again, after they've been reported in a daily digest.
+    pub fn reset_health(&mut self) {
+        self.health = PollHealth::default();
+    }
+}
+
+/// How polling a feed has gone since the last daily digest, or since starting.
+#[derive(Debug, Clone, Default)]
+pub struct PollHealth {
+    polls: u32,
+    failures: u32,
+    last_error: Option<String>,
+}
+
+impl PollHealth {
+    pub fn describe(&self) -> String {
+        match &self.last_error {
+            Some(err) => format!("{}/{} polls failed, last error: {}", self.failures, self.polls, err),
+            None => format!("{} polls, none failed", self.polls),
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use rnotifylib::message::{Level, Message};
+use rnotifylib::message::builder::MessageBuilder;
+use rnotifylib::message::component::Component;
+use rnotifylib::message::detail_builder::FormattedStringAppendable;
+use serde::{Deserialize, Serialize};
+use crate::NewPossibleEvent;
+use crate::resource::anticipation::{DfsIndustryNotification, IndustryNotificationType, relative_day};
+
+/// How long to remember events for after their date.
+const FORGET_AFTER_DAYS: i64 = 7;
+
+/// Where an event for one date has got to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventStage {
+    /// Nothing has been said about the date yet.
+    None,
+    Anticipated,
+    Published,
+    SupplierConfirmed,
+    Active,
+    Completed,
+    /// Cancelled after being published.
+    Cancelled,
+    /// Anticipated, but then not issued.
+    NotIssued,
+}
+
+impl Display for EventStage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventStage::None => write!(f, "not announced"),
+            EventStage::Anticipated => write!(f, "anticipated"),
+            EventStage::Published => write!(f, "published"),
+            EventStage::SupplierConfirmed => write!(f, "confirmed by our supplier"),
+            EventStage::Active => write!(f, "active"),
+            EventStage::Completed => write!(f, "completed"),
+            EventStage::Cancelled => write!(f, "cancelled"),
+            EventStage::NotIssued => write!(f, "not issued"),
+        }
+    }
+}
+
+impl EventStage {
+    /// The stage a new event moves this one to, or `None` if it isn't part of the lifecycle.
+    fn after(self, event: &NewPossibleEvent) -> Option<Self> {
+        match event {
+            NewPossibleEvent::Expected(_) => Some(Self::Anticipated),
+            NewPossibleEvent::Confirmed(_) => Some(Self::Published),
+            NewPossibleEvent::Cancelled(_) => match self {
+                // ESO use the same notification for both, but only a published event can be cancelled.
+                Self::None | Self::Anticipated => Some(Self::NotIssued),
+                _ => Some(Self::Cancelled),
+            },
+            NewPossibleEvent::OurSupplierConfirmed => Some(Self::SupplierConfirmed),
+            NewPossibleEvent::Test(_) | NewPossibleEvent::Unrecognised(_) => None,
+        }
+    }
+
+    fn can_move_to(self, next: Self) -> bool {
+        matches!((self, next),
+            (Self::None, Self::Anticipated | Self::Published | Self::NotIssued)
+            | (Self::Anticipated, Self::Published | Self::NotIssued)
+            | (Self::Published, Self::SupplierConfirmed | Self::Active | Self::Cancelled)
+            | (Self::SupplierConfirmed, Self::Active | Self::Cancelled)
+            | (Self::Active, Self::Completed)
+            // Not issued can be assumed when ESO don't follow up in time, so they may still publish.
+            | (Self::NotIssued, Self::Published))
+    }
+}
+
+/// An event moving from one stage to another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    date: NaiveDate,
+    from: EventStage,
+    to: EventStage,
+}
+
+impl Transition {
+    pub fn get_from(&self) -> EventStage {
+        self.from
+    }
+
+    pub fn get_to(&self) -> EventStage {
+        self.to
+    }
+
+    /// Whether the event could have got here from where it was, e.g. not going from cancelled back to anticipated.
+    pub fn is_valid(&self) -> bool {
+        self.from.can_move_to(self.to)
+    }
+
+    /// Describes what happened, e.g. "Anticipated notice for tomorrow (Tue 24 Jan) was withdrawn".
+    pub fn describe(&self, today: &NaiveDate) -> String {
+        let day = relative_day(&self.date, today);
+        let day = day.strip_prefix("on ").unwrap_or(&day);
+        match (self.from, self.to) {
+            (EventStage::Anticipated, EventStage::NotIssued) => format!("Anticipated notice for {} was withdrawn", day),
+            (_, EventStage::Cancelled) => format!("Published event for {} was cancelled", day),
+            _ if !self.is_valid() => format!("Event for {} went from {} to {}, which wasn't expected", day, self.from, self.to),
+            _ => format!("Event for {} is now {}", day, self.to),
+        }
+    }
+
+    /// A warning to send instead of the usual alert, as something odd has happened.
+    pub fn create_invalid_message(&self, feed: &str) -> Message {
+        let description = self.describe(&chrono::Local::now().date_naive());
+        let mut message_builder = MessageBuilder::new();
+        message_builder
+            .level(Level::Warn)
+            .title(format!("Unexpected DFS notification - {}", feed))
+            .body(|body| {
+                body.raw(format!("{} - {}", description, feed));
+                body.text_block(|block| {
+                    block.append_plain(format!("{}\n", description));
+                    block.append_plain("This hasn't been sent as a new event, please check the notification table.");
+                });
+            })
+            .component(Component::from("dfs_alert/invalid_transition"))
+            .author("dfs_alert");
+        message_builder.build()
+    }
+}
+
+/// ESO said they would follow up on an anticipated event, but haven't.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoEvent {
+    date: NaiveDate,
+    follow_up: NaiveDateTime,
+}
+
+impl NoEvent {
+    pub fn create_message(&self, feed: &str) -> Message {
+        let today = chrono::Local::now().date_naive();
+        let day = relative_day(&self.date, &today);
+        let summary = format!("No DFS event was published {} — ESO said they would confirm by {}", day, self.follow_up.format("%H:%M"));
+        let mut message_builder = MessageBuilder::new();
+        message_builder
+            .level(Level::Info)
+            .title(format!("No DFS event published - {}", feed))
+            .body(|body| {
+                body.raw(format!("{} - {}", summary, feed));
+                body.text_block(|block| {
+                    block.append_plain(format!("{}\n", summary));
+                    block.append_plain("You'll still be told if it is published later.");
+                });
+            })
+            .component(Component::from("dfs/no_event"))
+            .author("dfs_alert");
+        message_builder.build()
+    }
+}
+
+/// The stage of each event on a feed, keyed by the date it is for. Kept in saved state.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct EventLifecycle {
+    #[serde(default)]
+    events: BTreeMap<NaiveDate, EventStage>,
+}
+
+impl EventLifecycle {
+    /// The stage of the event for the date, if there is one.
+    pub fn get_stage(&self, date: &NaiveDate) -> Option<EventStage> {
+        self.events.get(date).copied()
+    }
+
+    /// Moves the event for the date on, returning what happened.
+    /// Returns `None` if the stage didn't change or the event isn't part of the lifecycle, e.g. a test.
+    /// Invalid transitions are still recorded, so that later notifications are compared with the latest.
+    pub fn observe(&mut self, date: NaiveDate, event: &NewPossibleEvent) -> Option<Transition> {
+        let from = self.events.get(&date).copied().unwrap_or(EventStage::None);
+        let to = from.after(event).filter(|to| to != &from)?;
+        self.events.insert(date, to);
+        Some(Transition {
+            date,
+            from,
+            to,
+        })
+    }
+
+    /// If the notification is an anticipated notice that ESO said they would follow up on, and they still haven't
+    /// after the grace period, the event is marked as not issued. Returns what to alert about the first time only.
+    /// Newer notices don't give a time to follow up by, so `default_follow_up` is used, on the next day if the notice came after it.
+    pub fn expire_anticipated(&mut self, notification: &DfsIndustryNotification, default_follow_up: NaiveTime, grace: Duration, now: &NaiveDateTime) -> Option<NoEvent> {
+        if notification.get_type() != &IndustryNotificationType::RequirementAnticipated {
+            return None;
+        }
+        let date = notification.get_event_date()?;
+        let when = notification.get_when();
+        let follow_up = match notification.get_status_details().get_follow_up() {
+            Some(follow_up) => when.date().and_time(*follow_up),
+            None if when.time() < default_follow_up => when.date().and_time(default_follow_up),
+            None => (when.date() + Duration::days(1)).and_time(default_follow_up),
+        };
+        let stage = self.events.get_mut(&date)?;
+        if *stage != EventStage::Anticipated || *now < follow_up + grace {
+            return None;
+        }
+        *stage = EventStage::NotIssued;
+        Some(NoEvent {
+            date,
+            follow_up,
+        })
+    }
+
+    /// Moves published events on as their windows start and finish, returning whether anything changed.
+    pub fn advance(&mut self, windows: &[(NaiveDateTime, NaiveDateTime)], now: &NaiveDateTime) -> bool {
+        let before = self.events.clone();
+        let forget_before = now.date() - Duration::days(FORGET_AFTER_DAYS);
+        self.events.retain(|date, _| date >= &forget_before);
+        for (start, end) in windows {
+            if let Some(stage) = self.events.get_mut(&start.date()) {
+                let due = if now >= end {
+                    EventStage::Completed
+                } else if now >= start {
+                    EventStage::Active
+                } else {
+                    continue;
+                };
+                match (*stage, due) {
+                    (EventStage::Published | EventStage::SupplierConfirmed | EventStage::Active, EventStage::Completed)
+                    | (EventStage::Published | EventStage::SupplierConfirmed, EventStage::Active) => *stage = due,
+                    _ => {}
+                }
+            }
+        }
+        before != self.events
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, NaiveDate, NaiveTime};
+    use crate::lifecycle::{EventLifecycle, EventStage};
+    use crate::NewPossibleEvent;
+    use crate::resource::anticipation::{DfsIndustryNotification, IndustryNotificationType};
+
+    fn notification(notification_type: IndustryNotificationType, date: NaiveDate) -> DfsIndustryNotification {
+        DfsIndustryNotification::new(notification_type, date.and_hms_opt(10, 0, 0).unwrap(), String::new(), Some(date))
+    }
+
+    #[test]
+    fn test_withdrawn() {
+        let date = NaiveDate::from_ymd_opt(2023, 1, 24).unwrap();
+        let mut lifecycle = EventLifecycle::default();
+        let anticipated = NewPossibleEvent::Expected(notification(IndustryNotificationType::RequirementAnticipated, date));
+        let transition = lifecycle.observe(date, &anticipated).expect("Should move to anticipated");
+        assert!(transition.is_valid());
+        assert_eq!(lifecycle.observe(date, &anticipated), None);
+
+        let cancelled = NewPossibleEvent::Cancelled(notification(IndustryNotificationType::RequirementCancelled, date));
+        let transition = lifecycle.observe(date, &cancelled).expect("Should be withdrawn");
+        assert_eq!(transition.get_to(), EventStage::NotIssued);
+        assert_eq!(transition.describe(&(date - Duration::days(1))), "Anticipated notice for tomorrow (Tue 24 Jan) was withdrawn");
+
+        let transition = lifecycle.observe(date, &anticipated).expect("Should still be recorded");
+        assert!(!transition.is_valid());
+    }
+
+    #[test]
+    fn test_no_event() {
+        let date = NaiveDate::from_ymd_opt(2023, 1, 23).unwrap();
+        let anticipated = DfsIndustryNotification::new(IndustryNotificationType::RequirementAnticipated, date.and_hms_opt(10, 0, 0).unwrap(),
+                                                       "This is an indication that a DFS Service Requirement might be published today at 14:30.".to_owned(), None);
+        let mut lifecycle = EventLifecycle::default();
+        lifecycle.observe(date, &NewPossibleEvent::Expected(anticipated.clone()));
+
+        let grace = Duration::minutes(30);
+        // Not the time given in the description.
+        let default_follow_up = NaiveTime::from_hms_opt(16, 0, 0).unwrap();
+        let follow_up = date.and_hms_opt(14, 30, 0).unwrap();
+        assert_eq!(lifecycle.expire_anticipated(&anticipated, default_follow_up, grace, &(follow_up + Duration::minutes(29))), None);
+        assert!(lifecycle.expire_anticipated(&anticipated, default_follow_up, grace, &(follow_up + grace)).is_some());
+        assert_eq!(lifecycle.events.get(&date), Some(&EventStage::NotIssued));
+        assert_eq!(lifecycle.expire_anticipated(&anticipated, default_follow_up, grace, &(follow_up + grace)), None);
+    }
+
+    #[test]
+    fn test_no_event_without_follow_up_time() {
+        // 2023 onwards notices give the date in its own column and no time in the description.
+        let date = NaiveDate::from_ymd_opt(2023, 12, 15).unwrap();
+        let anticipated = DfsIndustryNotification::new(IndustryNotificationType::RequirementAnticipated, (date - Duration::days(1)).and_hms_opt(15, 0, 0).unwrap(),
+                                                       "DFS Requirement Anticipated".to_owned(), Some(date));
+        let mut lifecycle = EventLifecycle::default();
+        lifecycle.observe(date, &NewPossibleEvent::Expected(anticipated.clone()));
+
+        // Issued after the default follow up time, so it's the next day's.
+        let grace = Duration::minutes(30);
+        let default_follow_up = NaiveTime::from_hms_opt(14, 30, 0).unwrap();
+        let follow_up = date.and_time(default_follow_up);
+        assert_eq!(lifecycle.expire_anticipated(&anticipated, default_follow_up, grace, &(follow_up - Duration::hours(12))), None);
+        assert!(lifecycle.expire_anticipated(&anticipated, default_follow_up, grace, &(follow_up + grace)).is_some());
+        assert_eq!(lifecycle.events.get(&date), Some(&EventStage::NotIssued));
+    }
+
+    #[test]
+    fn test_advance() {
+        let date = NaiveDate::from_ymd_opt(2023, 1, 24).unwrap();
+        let start = date.and_hms_opt(17, 0, 0).unwrap();
+        let windows = vec![(start, start + Duration::hours(1))];
+        let mut lifecycle = EventLifecycle::default();
+        lifecycle.observe(date, &NewPossibleEvent::Confirmed(notification(IndustryNotificationType::RequirementPublished, date)));
+
+        assert!(!lifecycle.advance(&windows, &(start - Duration::minutes(1))));
+        assert!(lifecycle.advance(&windows, &start));
+        assert_eq!(lifecycle.events.get(&date), Some(&EventStage::Active));
+        assert!(lifecycle.advance(&windows, &(start + Duration::hours(1))));
+        assert_eq!(lifecycle.events.get(&date), Some(&EventStage::Completed));
+    }
+}
@@ -1,28 +1,64 @@
+use std::collections::BTreeSet;
 use std::error::Error;
 use std::time::Duration;
-use chrono::{NaiveDate, NaiveTime};
+use chrono::{Local, NaiveDate, NaiveTime};
 use rnotifylib::config::Config;
 use rnotifylib::message::builder::MessageBuilder;
 use rnotifylib::message::detail_builder::FormattedStringAppendable;
-use rnotifylib::message::Level;
+use rnotifylib::message::{Level, Message};
 use rnotifylib::message_router::MessageRouter;
 use serde::{Deserialize, Deserializer};
-use serde::de::{Error as SerdeError, Unexpected};
+use serde::de::{Error as SerdeError, IntoDeserializer, Unexpected};
 use crate::resource::{AvailableResources, PreviousResources};
-use crate::resource::anticipation::{DfsIndustryNotification, IndustryNotificationResource, IndustryNotificationType};
+use crate::resource::anticipation::{DfsIndustryNotification, IndustryNotificationType};
 use crate::resource::details::CheckedDfsSupplierResource;
-use crate::saved_data::{AllSavedData, SavedData};
+use crate::resource::requirement::{next_window, ServiceRequirement, ServiceRequirementResource};
+use crate::resource::schema::{HeaderAliases, INDUSTRY_NOTIFICATION_SCHEMA, SchemaDrift, SERVICE_REQUIREMENT_SCHEMA, UTILISATION_REPORT_SCHEMA};
+use crate::resource::utilisation::{create_unknown_suppliers_message, EventOutcome, unknown_participants, UtilisationRow};
+use crate::config::DfsAlertConfig;
+use crate::consumption::{ConsumptionSource, MAX_LOOKBACK_DAYS, Turndown};
+use crate::daily::DailyDigest;
+use crate::discovery::Discovery;
+use crate::feed::Feed;
+use crate::resource::digest::ResourceDigest;
+use crate::resource::matcher::{ResourceChange, ResourceMatchers};
+use crate::mqtt::MqttPublisher;
+use crate::profile::Profile;
+use crate::quiet::QuietRouter;
+use crate::rate_limit::RateLimiter;
+use crate::region::Region;
+use crate::reminder::{EventWindow, ReminderState};
+use crate::saved_data::AllSavedData;
+use crate::service::Shutdown;
+use crate::supplier::SupplierRegistry;
+use crate::template::Templates;
+use crate::webhook::{WebhookPayload, Webhooks};
 
+mod config;
+mod consumption;
+mod daily;
+mod discovery;
+mod feed;
+mod lifecycle;
+mod mqtt;
+mod profile;
+mod quiet;
+mod rate_limit;
+mod region;
+mod reminder;
+mod reward;
 mod resource;
 mod saved_data;
+mod service;
+mod supplier;
+mod template;
+mod webhook;
 
 const OUR_SUPPLIER: &str = "OctopusEnergyLimited";
 const OUR_REGION: &str = "East Midlands";
 // https://data.nationalgrideso.com/dfs/demand-flexibility-service-live-events/r/dfs_utilisation_report_-_live
 
-const LIVE_URL_2022: &str = "https://api.nationalgrideso.com/api/3/action/datapackage_show?id=demand-flexibility-service-live-events";
-const TEST_URL_2022: &str = "https://api.nationalgrideso.com/api/3/action/datapackage_show?id=demand-flexibility-service-test-events";
-const NEW_URL_2023: &str  = "https://api.nationalgrideso.com/api/3/action/datapackage_show?id=demand-flexibility-service";
+const POLL_INTERVAL: Duration = Duration::from_millis(30*20*1000);
 
 #[tokio::main]
 async fn main() {
@@ -33,24 +69,90 @@ async fn main() {
 
     let message_router = MessageRouter::from_config(routing_config);
 
-    let i_notification_resource = IndustryNotificationResource::default();
+    let config = config::load_config()
+        .expect("dfs_alert.toml format invalid.");
+    let mut shutdown = Shutdown::listen()
+        .expect("Failed to listen for signals");
+    let webhooks = Webhooks::new(config.get_webhooks().clone());
 
-    let mut live_resources = PreviousResources::create(i_notification_resource.clone());
-    let mut test_resources = PreviousResources::create(i_notification_resource.clone());
-    let mut new_2023_resources = PreviousResources::create(i_notification_resource);
+    if std::env::args().any(|arg| arg == "--test-webhooks") {
+        let sent = webhooks.send_sample().await;
+        println!("Sent sample payload to {}/{} webhooks", sent, config.get_webhooks().len());
+        return;
+    }
+
+    let mqtt = config.get_mqtt().clone().map(MqttPublisher::connect);
+    if let Some(mqtt) = &mqtt {
+        if let Err(err) = mqtt.publish_discovery().await {
+            eprintln!("Failed to publish MQTT discovery: {}", err);
+        }
+    }
+
+    let region = match config.get_region() {
+        Some(region_config) => match Region::resolve(region_config).await {
+            Ok(region) => {
+                println!("Only alerting about requirements for {}", region.get_name());
+                Some(region)
+            }
+            Err(err) => {
+                eprintln!("Failed to work out region, requirements won't be filtered: {}", err);
+                None
+            }
+        },
+        None => None,
+    };
+
+    let suppliers = SupplierRegistry::new(config.get_suppliers());
+    let mut profiles = vec![];
+    for profile_config in config.get_profiles() {
+        let profile = Profile::load(profile_config, config.get_levels(), config.get_reward(), &region, config.get_quiet_hours(), &suppliers).await
+            .expect("Failed to load profile");
+        profiles.push(profile);
+    }
+    if profiles.is_empty() {
+        profiles.push(Profile::default_profile(config.get_levels(), config.get_reward(), &region, &suppliers));
+    }
+    for profile in &profiles {
+        println!("Alerting profile {}", profile.describe());
+    }
+
+    let outputs = Outputs {
+        router: QuietRouter::new(message_router, config.get_quiet_hours().clone()),
+        webhooks,
+        mqtt,
+        profiles,
+        suppliers,
+        templates: Templates::new(config.get_templates())
+            .expect("Invalid message template"),
+    };
+
+    let mut feeds = Feed::built_in();
 
+    let mut saved_reminders = ReminderState::default();
+    let mut known_packages = BTreeSet::new();
+    let mut turndowns = vec![];
+    let mut last_daily_digest = None;
     let state = load_state();
+    let state_loaded = state.is_ok();
     match state {
         Ok(saved_data) => {
             println!("Loaded previous state: {:?}", saved_data);
-            if let Some(old) = saved_data.get_live().get_industry_notification() {
-                live_resources.get_anticipated().set(old.clone())
+            for package in saved_data.get_discovered().keys() {
+                feeds.push(Feed::new(package, package, feed::is_test_package(package)));
             }
-            if let Some(old) = saved_data.get_test().get_industry_notification() {
-                test_resources.get_anticipated().set(old.clone());
+            for feed in &mut feeds {
+                if let Some(saved) = saved_data.get_feed(feed.get_package()) {
+                    feed.get_resources_mut().restore(saved);
+                }
             }
-            if let Some(old) = saved_data.get_new().get_industry_notification() {
-                new_2023_resources.get_anticipated().set(old.clone());
+            saved_reminders = saved_data.get_reminders().clone();
+            known_packages = saved_data.get_known_packages().clone();
+            turndowns = saved_data.get_turndowns().clone();
+            last_daily_digest = *saved_data.get_last_daily_digest();
+            for (key, router) in outputs.routers() {
+                if let Some(queued) = saved_data.get_queued().get(key) {
+                    router.restore(queued.clone());
+                }
             }
         }
         Err(err) => {
@@ -58,76 +160,458 @@ async fn main() {
         }
     }
 
+    let consumption = config.get_meter().as_ref()
+        .map(|meter| meter.create_source(config.get_csv_aliases()).expect("Invalid [meter] config"));
+
+    let mut reminder_state = saved_reminders;
+    let reminder_config = config.get_reminders().clone();
+    let mut discovery = config.get_discovery().clone()
+        .map(|discovery_config| Discovery::new(discovery_config, known_packages));
+    let limiter = RateLimiter::new(config.get_request_interval());
+
+    if config.is_startup_message() {
+        let now = Local::now().naive_local();
+        match outputs.router.route_now(&service::create_started_message(&mut feeds, &region, state_loaded, &now)) {
+            Ok(amt) => {
+                println!("Informed {} destinations that dfs_alert started", amt);
+            }
+            Err(send_errors) => {
+                eprintln!("Errors informing some destinations that dfs_alert started:");
+                eprintln!("{}", send_errors);
+            }
+        }
+    }
+
     loop {
         let mut changed = false;
-        changed |= run("live (old)", &mut live_resources, LIVE_URL_2022, &message_router).await;
-        changed |= run("test (old)", &mut test_resources, TEST_URL_2022, &message_router).await;
-        changed |= run("new always live", &mut new_2023_resources, NEW_URL_2023, &message_router).await;
-
-        if changed {
-            let to_save = AllSavedData {
-                test: SavedData {
-                    industry_notification: test_resources.get_anticipated().get_last_data().clone(),
-                },
-                live: SavedData {
-                    industry_notification: live_resources.get_anticipated().get_last_data().clone(),
-                },
-                new_2023: SavedData {
-                    industry_notification: new_2023_resources.get_anticipated().get_last_data().clone(),
+        if let Some(discovery) = &mut discovery {
+            changed |= discover(discovery, &mut feeds, &outputs).await;
+        }
+        // Due feeds are polled together, but on the one task, so only one of them changes state at a time.
+        let now = Local::now().naive_local();
+        let polls = feeds.iter_mut()
+            .filter(|feed| feed.is_due(&now))
+            .map(|feed| {
+                feed.schedule_next(now + config.get_poll_interval(feed.get_package()));
+                run(feed, &config, &region, &outputs, &limiter)
+            });
+        for feed_changed in futures::future::join_all(polls).await {
+            changed |= feed_changed;
+        }
+
+        let now = Local::now().naive_local();
+        for feed in &mut feeds {
+            let test = feed.is_test();
+            let name = feed.get_name().to_owned();
+            let resources = feed.get_resources_mut();
+            changed |= resources.advance_lifecycle(&now);
+            if test {
+                continue;
+            }
+            if let Some(no_event) = resources.check_no_event(config.get_default_follow_up(), config.get_no_event_grace(), &now) {
+                changed = true;
+                outputs.route_to_profiles(&no_event.create_message(&name), "that no event was published", |_| true);
+            }
+        }
+
+        // Reminders and MQTT state are only for live events.
+        let mut windows = vec![];
+        for feed in feeds.iter_mut().filter(|feed| !feed.is_test()) {
+            let name = feed.get_name().to_owned();
+            let resources = feed.get_resources_mut();
+            if let Some(notification) = resources.get_anticipated().get_last_data() {
+                changed |= reminder_state.observe(&name, notification);
+            }
+            let requirements = resources.get_requirements();
+            windows.extend(requirements.get_windows().into_iter()
+                .map(|(start, end)| EventWindow::new(&name, start, end).with_areas(requirements.get_areas(&start.date()))));
+        }
+        // The turn-down and MQTT state are for the household in the top level region.
+        let local_windows: Vec<EventWindow> = windows.iter()
+            .filter(|window| Region::applies_to_any(&region, window.get_areas()))
+            .cloned()
+            .collect();
+
+        if let Some(consumption) = &consumption {
+            changed |= check_turndown(consumption.as_ref(), &local_windows, &mut turndowns, &outputs).await;
+        }
+
+        if let Some(daily_digest) = config.get_daily_digest() {
+            let now = Local::now().naive_local();
+            if daily_digest.is_due(&last_daily_digest, &now) {
+                match outputs.router.route(&DailyDigest::new(&mut feeds, &region, &now).create_message()) {
+                    Ok(amt) => {
+                        println!("Sent daily digest to {} destinations", amt);
+                        // Health is counted from one digest to the next, so it is kept until one gets through.
+                        for feed in &mut feeds {
+                            feed.reset_health();
+                        }
+                    }
+                    Err(send_errors) => {
+                        eprintln!("Errors sending daily digest to some destinations:");
+                        eprintln!("{}", send_errors);
+                    }
                 }
-            };
-            println!("State changed, saving {:?}", to_save);
-            match save_state(&to_save) {
-                Ok(_) => {
-                    println!("Successfully saved state");
+                // Still only tried once a day, so that the destinations that did get it aren't sent it again.
+                last_daily_digest = Some(now.date());
+                changed = true;
+            }
+        }
+
+        // Still woken up every poll interval, for discovery and the lifecycle.
+        let next_poll = feeds.iter()
+            .filter_map(Feed::get_next_poll)
+            .chain(std::iter::once(Local::now().naive_local() + chrono::Duration::from_std(POLL_INTERVAL).unwrap()))
+            .min()
+            .unwrap();
+        loop {
+            let now = Local::now().naive_local();
+            if let Some(reminder_config) = &reminder_config {
+                changed |= reminder_state.send_due(reminder_config, &windows, &now, |reminder, window| {
+                    outputs.route_to_profiles(&reminder.create_message(), "of reminder", |profile| profile.wants_areas(window.get_areas()));
+                });
+            }
+            for (_, router) in outputs.routers() {
+                changed |= router.release_due(&now);
+                changed |= router.take_changed();
+            }
+            // Republished every time it wakes up, so that `active` changes as the window starts and ends.
+            if let Some(mqtt) = &outputs.mqtt {
+                let next = next_window(local_windows.iter().map(|window| (*window.get_start(), *window.get_end())), &now);
+                if let Err(err) = mqtt.publish_state(next, &now).await {
+                    eprintln!("Failed to publish MQTT state: {}", err);
                 }
-                Err(err) => {
-                    eprintln!("Failed to save state: {:?}", err);
+            }
+
+            if changed {
+                println!("State changed, saving");
+                save_all(&feeds, &reminder_state, &discovery, &turndowns, last_daily_digest, &outputs);
+                changed = false;
+            }
+
+            if now >= next_poll {
+                break;
+            }
+            // Wake up early if a reminder is due, quiet hours end or an event starts or ends before the next poll.
+            let wake = reminder_config.as_ref()
+                .and_then(|reminder_config| reminder_state.next_due(reminder_config, &windows, &now))
+                .into_iter()
+                .chain(outputs.routers().filter_map(|(_, router)| router.next_release()))
+                .chain(windows.iter()
+                    .flat_map(|window| [*window.get_start(), *window.get_end()])
+                    .filter(|time| time > &now))
+                .filter(|due| due < &next_poll)
+                .min()
+                .unwrap_or(next_poll);
+            // Signals are only acted on here, once the poll has finished and the state has been saved.
+            tokio::select! {
+                _ = tokio::time::sleep((wake - now).to_std().unwrap_or_default()) => {}
+                signal = shutdown.recv() => {
+                    println!("Received {}, stopping", signal);
+                    // Saved again in case anything has been held back since, so that it is sent after a restart.
+                    save_all(&feeds, &reminder_state, &discovery, &turndowns, last_daily_digest, &outputs);
+                    if config.is_shutdown_message() {
+                        match outputs.router.route_now(&service::create_stopping_message(signal)) {
+                            Ok(amt) => {
+                                println!("Informed {} destinations that dfs_alert is stopping", amt);
+                            }
+                            Err(send_errors) => {
+                                eprintln!("Errors informing some destinations that dfs_alert is stopping:");
+                                eprintln!("{}", send_errors);
+                            }
+                        }
+                    }
+                    return;
                 }
             }
         }
+    }
+}
+
+/// Looks for new DFS datapackages if it is time to, alerting about any that are found
+/// and watching them if configured to. Returns whether the state changed.
+async fn discover(discovery: &mut Discovery, feeds: &mut Vec<Feed>, outputs: &Outputs) -> bool {
+    let now = Local::now().naive_local();
+    if !discovery.is_due(&now) {
+        return false;
+    }
+    println!("Searching for new DFS datapackages");
+    let watched: Vec<&str> = feeds.iter().map(Feed::get_package).collect();
+    let before = discovery.get_known_packages().len();
+    let new = match discovery.run(&now, &watched).await {
+        Ok(new) => new,
+        Err(err) => {
+            eprintln!("Failed to search for new DFS datapackages: {}", err);
+            return false;
+        }
+    };
+    let changed = discovery.get_known_packages().len() != before;
+
+    let auto_watch = discovery.get_config().is_auto_watch();
+    for package in new {
+        match outputs.router.route(&package.create_message(auto_watch)) {
+            Ok(amt) => {
+                println!("Informed {} destinations of new datapackage {}", amt, package.get_name());
+            }
+            Err(send_errors) => {
+                eprintln!("Errors informing some destinations of new datapackage:");
+                eprintln!("{}", send_errors);
+            }
+        }
+        if auto_watch {
+            feeds.push(Feed::new(package.get_name(), package.get_name(), package.is_test()));
+        }
+    }
+    changed
+}
+
+/// How long after an event to keep trying to work out its turn-down, as meter readings can take a while to turn up.
+const TURNDOWN_WAIT_DAYS: i64 = 7;
 
-        tokio::time::sleep(Duration::from_millis(30*20*1000)).await;
+/// Works out the turn-down of finished events that haven't had it reported, once the meter readings cover them.
+/// Returns whether any were reported.
+async fn check_turndown(consumption: &dyn ConsumptionSource, windows: &[EventWindow], turndowns: &mut Vec<Turndown>, outputs: &Outputs) -> bool {
+    let now = Local::now().naive_local();
+    let mut due: Vec<&EventWindow> = windows.iter()
+        .filter(|window| window.get_end() < &now && now - *window.get_end() < chrono::Duration::days(TURNDOWN_WAIT_DAYS))
+        .filter(|window| !turndowns.iter().any(|turndown| turndown.get_start() == window.get_start()))
+        .collect();
+    // Feeds can share a window, e.g. the 2022 live feed and the 2023 one, but it is only one turn-down.
+    due.sort_by_key(|window| *window.get_start());
+    due.dedup_by_key(|window| *window.get_start());
+    if due.is_empty() {
+        return false;
     }
+    // Enough to cover the reference days of the earliest event.
+    let from = due.iter().map(|window| *window.get_start()).min().unwrap() - chrono::Duration::days(MAX_LOOKBACK_DAYS);
+    let to = due.iter().map(|window| *window.get_end()).max().unwrap();
+    let readings = match consumption.fetch(from, to).await {
+        Ok(readings) => readings,
+        Err(err) => {
+            eprintln!("Failed to read meter readings: {}", err);
+            return false;
+        }
+    };
+    let event_dates: BTreeSet<NaiveDate> = windows.iter().map(|window| window.get_start().date()).collect();
+    let mut changed = false;
+    for window in due {
+        let turndown = match Turndown::calculate(&readings, *window.get_start(), *window.get_end(), &event_dates) {
+            Some(turndown) => turndown,
+            None => {
+                println!("Not enough meter readings yet for the event at {}", window.get_start());
+                continue;
+            }
+        };
+        outputs.route_to_profiles(&turndown.create_message(window.get_feed()), "of turn-down", |profile| profile.wants_areas(window.get_areas()));
+        turndowns.push(turndown);
+        changed = true;
+    }
+    changed
 }
 
 const STATE_FILE: &str = "state.json";
 
+/// Saves the state of every feed, along with everything else that is kept between runs.
+fn save_all(feeds: &[Feed], reminder_state: &ReminderState, discovery: &Option<Discovery>, turndowns: &[Turndown],
+            last_daily_digest: Option<NaiveDate>, outputs: &Outputs) {
+    let mut to_save = AllSavedData {
+        reminders: reminder_state.clone(),
+        known_packages: discovery.as_ref()
+            .map(|discovery| discovery.get_known_packages().clone())
+            .unwrap_or_default(),
+        turndowns: turndowns.to_vec(),
+        last_daily_digest,
+        queued: outputs.routers()
+            .map(|(key, router)| (key.to_owned(), router.get_queue()))
+            .filter(|(_, queue)| !queue.is_empty())
+            .collect(),
+        ..AllSavedData::default()
+    };
+    for feed in feeds {
+        to_save.set_feed(feed.get_package(), feed.get_resources().to_saved_data());
+    }
+    println!("Saving {:?}", to_save);
+    match save_state(&to_save) {
+        Ok(_) => {
+            println!("Successfully saved state");
+        }
+        Err(err) => {
+            eprintln!("Failed to save state: {:?}", err);
+        }
+    }
+}
+
 fn load_state() -> Result<AllSavedData, Box<dyn Error>> {
     let s = std::fs::read_to_string(STATE_FILE)?;
     Ok(serde_json::from_str(&s)?)
 }
 
+/// Writes to a temporary file first, so that the old state is kept if dfs_alert is killed mid-write.
 fn save_state(data: &AllSavedData) -> Result<(), Box<dyn Error>> {
     let s = serde_json::to_string(data)?;
-    Ok(std::fs::write(STATE_FILE, s)?)
+    let temp = format!("{}.tmp", STATE_FILE);
+    std::fs::write(&temp, s)?;
+    Ok(std::fs::rename(temp, STATE_FILE)?)
+}
+
+/// Everywhere that gets told about changes.
+struct Outputs {
+    router: QuietRouter,
+    webhooks: Webhooks,
+    mqtt: Option<MqttPublisher>,
+    /// Who gets event messages, each through their own router.
+    profiles: Vec<Profile>,
+    suppliers: SupplierRegistry,
+    templates: Templates,
+}
+
+impl Outputs {
+    /// The default router and every profile's own one, keyed by profile name.
+    fn routers(&self) -> impl Iterator<Item = (&str, &QuietRouter)> {
+        let profiles = self.profiles.iter()
+            .filter_map(|profile| Some((profile.get_name().as_deref()?, profile.get_own_router().as_ref()?)));
+        std::iter::once(("default", &self.router)).chain(profiles)
+    }
+
+    /// Routes the message once through the router of every profile that `wants` it.
+    /// Profiles without their own routing share the default router, which only gets it once.
+    fn route_to_profiles<F: Fn(&Profile) -> bool>(&self, message: &Message, what: &str, wants: F) {
+        let mut routed: Vec<&QuietRouter> = vec![];
+        for profile in self.profiles.iter().filter(|profile| wants(profile)) {
+            let router = profile.get_router(&self.router);
+            if routed.iter().any(|other| std::ptr::eq(*other, router)) {
+                continue;
+            }
+            routed.push(router);
+            match router.route(message) {
+                Ok(amt) => {
+                    println!("Informed {} destinations {} for {}", amt, what, profile.describe());
+                }
+                Err(send_errors) => {
+                    eprintln!("Errors informing some destinations {} for {}:", what, profile.describe());
+                    eprintln!("{}", send_errors);
+                }
+            }
+        }
+    }
 }
 
-async fn run(name: &str, resources: &mut PreviousResources, url: &str, router: &MessageRouter) -> bool {
+async fn run(feed: &mut Feed, config: &DfsAlertConfig, region: &Option<Region>, outputs: &Outputs, limiter: &RateLimiter) -> bool {
+    let name = feed.get_name().to_owned();
+    let name = name.as_str();
+    let test = feed.is_test();
+    let url = feed.get_url();
     println!("Running '{}'", name);
-    match check_for_changes(resources, url).await {
-        Ok(None) => {
+    let available = fetch_available_resources(&url, limiter).await;
+    // Reported before the rest of the check, as a resource disappearing can make it fail.
+    let mut known_updated = false;
+    if let Ok(available) = &available {
+        let (updated, resource_changes) = update_known_resources(feed.get_resources_mut(), available, config.get_resource_matchers());
+        known_updated = updated;
+        for change in resource_changes {
+            outputs.route_to_profiles(&change.create_message(name), "of file changes", |_| true);
+        }
+    }
+    let changes = match available {
+        Ok(available) => check_for_changes(feed.get_resources_mut(), available, config, limiter).await,
+        Err(err) => Err(err),
+    };
+    feed.record_poll(changes.as_ref().err().map(|err| err.to_string()));
+    let resources = feed.get_resources_mut();
+    if let Ok(Changes { schema_drift, digest, .. }) = &changes {
+        let messages = schema_drift.iter().map(|drift| drift.create_message(name))
+            .chain(digest.iter().map(|digest| digest.create_message(name)));
+        for message in messages {
+            outputs.route_to_profiles(&message, "of file changes", |_| true);
+        }
+    }
+    let mut results_updated = false;
+    if let Ok(Changes { utilisation: Some(rows), .. }) = &changes {
+        let unreported = resources.get_utilisation().get_unreported(rows);
+        if !unreported.is_empty() {
+            report_results(name, resources, &unreported, outputs);
+        }
+        // Only once the check has succeeded, so that results aren't lost if it fails.
+        results_updated = resources.get_utilisation().mark_reported(rows);
+    }
+    match changes {
+        Ok(Changes { event: None, state_updated, .. }) => {
             println!("Nothing changed on {} resource", name);
+            return state_updated || known_updated || results_updated;
         },
-        Ok(Some(change)) => {
-            let message = match change {
-                NewPossibleEvent::Expected(notification) => notification.create_message(name),
-                NewPossibleEvent::Confirmed(notification) => notification.create_message(name),
-                NewPossibleEvent::Cancelled(notification) => notification.create_message(name),
-                NewPossibleEvent::OurSupplierConfirmed => {
-                    todo!()
+        Ok(Changes { event: Some(change), .. }) => {
+            // Everything on a test feed is a test, whatever type of notification it is.
+            // Unrecognised notifications keep their type, so the flag is carried separately.
+            let change = if test { change.into_test() } else { change };
+            let test_event = test || matches!(change, NewPossibleEvent::Test(_));
+            let payload = WebhookPayload::from_event(name, &change, test_event);
+            let transition = payload.get_event_date()
+                .and_then(|date| resources.get_lifecycle().observe(date, &change));
+            if let Some(transition) = transition.as_ref().filter(|transition| !transition.is_valid()) {
+                let requirements = &*resources.get_requirements();
+                outputs.route_to_profiles(&transition.create_invalid_message(name), "of unexpected notification", |profile| {
+                    profile.wants(test_event, payload.get_event_date(), requirements)
+                });
+                return true;
+            }
+            let payload = payload.with_transition(transition.as_ref());
+            let notes: Vec<String> = transition.iter()
+                .map(|transition| transition.describe(&Local::now().date_naive()))
+                .collect();
+            if let (Some(mqtt), Some(notification)) = (&outputs.mqtt, resources.get_anticipated().get_last_data()) {
+                if let Err(err) = mqtt.publish_notification(name, notification).await {
+                    eprintln!("Failed to publish notification to MQTT: {}", err);
                 }
-                NewPossibleEvent::Test(notification) => notification.create_message("test (new)"),
-            };
-            match router.route(&message) {
-                Ok(amt) => {
-                    println!("Informed {} destinations", amt);
+            }
+            let requirements = resources.get_requirements().clone();
+            let component = change.get_component();
+            for profile in &outputs.profiles {
+                if !profile.wants(test_event, payload.get_event_date(), &requirements) {
+                    println!("Not telling {} about {:?}", profile.describe(), change);
+                    continue;
                 }
-                Err(send_errors) => {
-                    eprintln!("Errors informing some destinations:");
-                    eprintln!("{}", send_errors);
+                // The times and price can be different in the profile's region, and it can be with a different supplier.
+                let payload = with_requirements(payload.clone(), &requirements, profile.get_region());
+                let payload = with_supplier_status(payload, resources, profile.get_supplier());
+                let level = profile.get_levels().get_level(&change);
+                let label = profile.label(name);
+                let mut notes = notes.clone();
+                if let Some(price) = payload.get_price() {
+                    notes.push(format!("Guaranteed acceptance price: £{:.2}/MWh", price));
+                    if let Some(reward) = profile.get_reward() {
+                        notes.push(reward.describe(*price));
+                    }
                 }
-            };
+                let message = match outputs.templates.create_message(&payload, &notes, level.clone(), component) {
+                    Some(message) => message,
+                    None => match &change {
+                        NewPossibleEvent::Expected(notification) => notification.create_message(&label, &notes, level, component),
+                        NewPossibleEvent::Confirmed(notification) => notification.create_message(&label, &notes, level, component),
+                        NewPossibleEvent::Cancelled(notification) => notification.create_message(&label, &notes, level, component),
+                        NewPossibleEvent::OurSupplierConfirmed => {
+                            todo!()
+                        }
+                        NewPossibleEvent::Test(notification) => {
+                            notification.create_message(&if test { label } else { profile.label("test (new)") }, &notes, level, component)
+                        },
+                        NewPossibleEvent::Unrecognised(notification) => notification.create_message(&label, &notes, level, component),
+                    },
+                };
+                match profile.get_router(&outputs.router).route(&message) {
+                    Ok(amt) => {
+                        println!("Informed {} destinations for {}", amt, profile.describe());
+                    }
+                    Err(send_errors) => {
+                        eprintln!("Errors informing some destinations for {}:", profile.describe());
+                        eprintln!("{}", send_errors);
+                    }
+                };
+            }
+            // Webhooks are for the household in the top level region, with the default supplier.
+            let payload = with_requirements(payload, &requirements, region);
+            let payload = with_supplier_status(payload, resources, &outputs.suppliers.identify(OUR_SUPPLIER).map(str::to_owned));
+            let sent = outputs.webhooks.send(&payload).await;
+            println!("Sent {} webhooks", sent);
             return true;
         }
         Err(err) => {
@@ -146,18 +630,62 @@ async fn run(name: &str, resources: &mut PreviousResources, url: &str, router: &
                     });
                 });
             let message = builder.build();
-            match router.route(&message) {
-                Ok(count) => {
-                    println!("Informed {} destinations of error.", count);
+            outputs.route_to_profiles(&message, "of error", |_| true);
+        }
+    }
+    known_updated
+}
+
+/// Fills in the event times and price from the requirements that apply to the region.
+fn with_requirements(payload: WebhookPayload, requirements: &ServiceRequirementResource, region: &Option<Region>) -> WebhookPayload {
+    match *payload.get_event_date() {
+        Some(date) => {
+            let requirements = requirements.in_region(region);
+            let window = requirements.get_window(&date);
+            let price = requirements.get_price(&date);
+            payload.with_window(window).with_price(price)
+        }
+        None => payload,
+    }
+}
+
+/// Fills in how the supplier is taking part in the event, which is only known once the results are in.
+fn with_supplier_status(payload: WebhookPayload, resources: &PreviousResources, supplier: &Option<String>) -> WebhookPayload {
+    match *payload.get_event_date() {
+        Some(date) => {
+            let status = resources.get_supplier_status(&date, supplier);
+            payload.with_supplier_status(status)
+        }
+        None => payload,
+    }
+}
+
+/// Tells each profile how the events in the new rows of the utilisation report went, and adds them to the history.
+fn report_results(name: &str, resources: &mut PreviousResources, rows: &[UtilisationRow], outputs: &Outputs) {
+    let unknown = unknown_participants(rows, &outputs.suppliers);
+    if !unknown.is_empty() {
+        outputs.route_to_profiles(&create_unknown_suppliers_message(&unknown, name), "of unknown suppliers", |_| true);
+    }
+
+    let dates: BTreeSet<NaiveDate> = rows.iter().map(|row| *row.get_date()).collect();
+    for date in dates {
+        for profile in &outputs.profiles {
+            let outcome = match EventOutcome::summarise(date, rows, profile.get_supplier(), &outputs.suppliers) {
+                Some(outcome) => outcome,
+                None => continue,
+            };
+            match profile.get_router(&outputs.router).route(&outcome.create_message(&profile.label(name), profile.get_reward())) {
+                Ok(amt) => {
+                    println!("Informed {} destinations of results for {}", amt, profile.describe());
                 }
-                Err(err) => {
-                    eprintln!("Failed to notify destinations of error checking DFS:");
-                    eprintln!("{}", err)
+                Err(send_errors) => {
+                    eprintln!("Errors informing some destinations of results for {}:", profile.describe());
+                    eprintln!("{}", send_errors);
                 }
             }
+            resources.record_outcome(outcome);
         }
     }
-    false
 }
 
 #[derive(Deserialize)]
@@ -166,73 +694,178 @@ struct RequestResult {
     result: AvailableResources,
 }
 
-async fn check_for_changes(previous: &mut PreviousResources, url: &str) -> Result<Option<NewPossibleEvent>, Box<dyn Error>> {
+/// What changed in a datapackage since it was last checked.
+struct Changes {
+    event: Option<NewPossibleEvent>,
+    /// Whether anything else that is saved has changed.
+    state_updated: bool,
+    /// Newly noticed changes to the format of the files.
+    schema_drift: Vec<SchemaDrift>,
+    /// Every resource modified since last time, if watching all of them.
+    digest: Option<ResourceDigest>,
+    /// Every row of the utilisation report, if it has changed since last time.
+    utilisation: Option<Vec<UtilisationRow>>,
+}
+
+async fn fetch_available_resources(url: &str, limiter: &RateLimiter) -> Result<AvailableResources, Box<dyn Error>> {
+    limiter.wait().await;
     let result: RequestResult = reqwest::get(url).await?.json().await?;
     if !result.success {
         return Err("Not a success!".into());
     }
-    let available_resources: AvailableResources = result.result;
-    println!("available_resources: {:?}", available_resources);
-    let anticipated = available_resources.get_dfs_industry_notification_resource()?;
+    println!("available_resources: {:?}", result.result);
+    Ok(result.result)
+}
+
+/// Remembers which resources match, returning whether that changed and the matching resources that appeared or disappeared.
+fn update_known_resources(previous: &mut PreviousResources, available_resources: &AvailableResources, matchers: &ResourceMatchers) -> (bool, Vec<ResourceChange>) {
+    let mut state_updated = false;
+    let mut resource_changes = vec![];
+    for (kind, matcher) in [("industry notification", matchers.get_industry_notification()),
+                            ("service requirement", matchers.get_service_requirement())] {
+        let (updated, change) = previous.update_known_resources(kind, &available_resources.find_matching(matcher));
+        state_updated |= updated;
+        resource_changes.extend(change);
+    }
+    (state_updated, resource_changes)
+}
+
+async fn check_for_changes(previous: &mut PreviousResources, available_resources: AvailableResources, config: &DfsAlertConfig, limiter: &RateLimiter) -> Result<Changes, Box<dyn Error>> {
+    let aliases = config.get_csv_aliases();
+    let matchers = config.get_resource_matchers();
+    let mut state_updated = false;
+
+    let anticipated = available_resources.get_dfs_industry_notification_resource(matchers.get_industry_notification())?;
     println!("anticipated: {:?}", anticipated);
     println!("previous: {:?}", previous.get_anticipated());
 
     //let supplier_details = available_resources.get_dfs_supplier_details_source()?;
 
+    // Read requirements first, so that the event times are known when alerting.
+    let mut schema_drift = vec![];
+    match available_resources.get_dfs_service_requirement_resource(matchers.get_service_requirement()) {
+        Ok(requirements) => {
+            if requirements.get_last_modified() > previous.get_requirements().get_last_checked() {
+                let (new_data, drift) = read_service_requirements(requirements.get_path(), aliases, limiter).await?;
+                schema_drift.extend(drift);
+                // Every region is kept, each profile only hears about the ones for its own region.
+                if let Some(new_data) = new_data {
+                    if previous.get_requirements().get_last_data() != &new_data {
+                        state_updated = true;
+                    }
+                    previous.get_requirements().update(new_data);
+                }
+            }
+        }
+        Err(err) => {
+            println!("No service requirements: {}", err);
+        }
+    }
+
+    let mut utilisation = None;
+    if let Ok(report) = available_resources.get_dfs_utilisation_report_resource(matchers.get_utilisation_report()) {
+        if report.get_last_modified() > previous.get_utilisation().get_last_checked() {
+            let (rows, drift) = read_utilisation_report(report.get_path(), aliases, limiter).await?;
+            schema_drift.extend(drift);
+            utilisation = Some(rows);
+        }
+    }
+
     let mut anticipated_event = None;
 
     if anticipated.get_last_modified() > previous.get_anticipated().get_last_checked() {
         // Anticipated changed.
-        let new_data = read_industry_notification_data(anticipated.get_path()).await?;
+        let (new_data, drift) = read_industry_notification_data(anticipated.get_path(), aliases, limiter).await?;
+        schema_drift.extend(drift);
 
         let previous_data = previous.get_anticipated();
 
-        if previous_data.get_last_data()
+        if let Some(new_data) = new_data.filter(|new_data| previous_data.get_last_data()
             .as_ref()
-            .filter(|old| old == &&new_data)
-            .is_none() {
+            .filter(|old| old == &new_data)
+            .is_none()) {
             // Data has actually changed.
             let new_event = match new_data.get_type() {
                 IndustryNotificationType::RequirementAnticipated => NewPossibleEvent::Expected(new_data.clone()),
                 IndustryNotificationType::RequirementPublished => NewPossibleEvent::Confirmed(new_data.clone()),
                 IndustryNotificationType::RequirementCancelled => NewPossibleEvent::Cancelled(new_data.clone()),
                 IndustryNotificationType::Test => NewPossibleEvent::Test(new_data.clone()),
+                IndustryNotificationType::Unknown(_) => NewPossibleEvent::Unrecognised(new_data.clone()),
             };
             anticipated_event = Some(new_event);
             previous_data.update(new_data);
         }
     }
 
+    // Only once nothing else can fail, so that the digest isn't lost.
+    let mut digest = None;
+    if config.is_watch_all_resources() {
+        let (updated, new_digest) = previous.update_resource_timestamps(available_resources.get_resources());
+        state_updated |= updated;
+        digest = new_digest;
+    }
+
+    let schema_drift = previous.report_schema_drift(schema_drift);
+
     if anticipated_event.is_some() {
-        return Ok(anticipated_event);
+        return Ok(Changes {
+            event: anticipated_event,
+            state_updated,
+            schema_drift,
+            digest,
+            utilisation,
+        });
     }
 
     //if supplier_details.get_last_modified() > previous.get_supplier_details().get_last_checked() {
     //    let parsed = read_supplier_details(anticipated.get_path());
     //}
 
-    Ok(None)
+    Ok(Changes {
+        event: None,
+        state_updated,
+        schema_drift,
+        digest,
+        utilisation,
+    })
 }
 
-async fn read_industry_notification_data(url: &str) -> Result<DfsIndustryNotification, Box<dyn Error>> {
+/// Reads the service requirements, or `None` if the file is missing a required column.
+async fn read_service_requirements(url: &str, aliases: &HeaderAliases, limiter: &RateLimiter) -> Result<(Option<Vec<ServiceRequirement>>, Option<SchemaDrift>), Box<dyn Error>> {
+    limiter.wait().await;
     let string = reqwest::get(url).await?.text().await?;
-    let string = string.trim().replace(",,,,,", ""); // They decided pad out their csv file...
-    //println!("{}", string);
-    let mut reader = csv::Reader::from_reader(string.as_bytes());
-    let mut parsed = vec![];
-    for record in reader.deserialize() {
-        let record: DfsIndustryNotificationResponse = record?;
-        //println!("{:?}", record);
-        parsed.push(record);
+    let (parsed, drift) = SERVICE_REQUIREMENT_SCHEMA.read(&string, aliases)?.into_parts();
+    if drift.is_some() && parsed.is_empty() {
+        return Ok((None, drift));
     }
+    Ok((Some(parsed), drift))
+}
+
+async fn read_utilisation_report(url: &str, aliases: &HeaderAliases, limiter: &RateLimiter) -> Result<(Vec<UtilisationRow>, Option<SchemaDrift>), Box<dyn Error>> {
+    limiter.wait().await;
+    let string = reqwest::get(url).await?.text().await?;
+    Ok(UTILISATION_REPORT_SCHEMA.read(&string, aliases)?.into_parts())
+}
+
+/// Reads the latest notification, or `None` if the file is missing a required column.
+async fn read_industry_notification_data(url: &str, aliases: &HeaderAliases, limiter: &RateLimiter) -> Result<(Option<DfsIndustryNotification>, Option<SchemaDrift>), Box<dyn Error>> {
+    limiter.wait().await;
+    let string = reqwest::get(url).await?.text().await?;
+    let (mut parsed, drift) = INDUSTRY_NOTIFICATION_SCHEMA.read::<DfsIndustryNotificationResponse>(&string, aliases)?
+        .into_parts();
     if parsed.is_empty() {
-        panic!("No records returned");
+        if drift.is_some() {
+            return Ok((None, drift));
+        }
+        return Err("No records returned".into());
     }
     let first = parsed.remove(0);
-    Ok(first.into_notification()?)
+    Ok((Some(first.into_notification()?), drift))
 }
 
-fn read_supplier_details(url: &str) -> CheckedDfsSupplierResource {
+// Not used until supplier details are read, see check_for_changes.
+#[allow(dead_code)]
+fn read_supplier_details(_url: &str) -> CheckedDfsSupplierResource {
     todo!()
 }
 
@@ -242,8 +875,37 @@ enum NewPossibleEvent {
     Expected(DfsIndustryNotification),
     Confirmed(DfsIndustryNotification),
     Cancelled(DfsIndustryNotification),
+    /// Not constructed until supplier details are read.
+    #[allow(dead_code)]
     OurSupplierConfirmed,
     Test(DfsIndustryNotification),
+    /// A notification with a type we don't know about, which someone should look at.
+    Unrecognised(DfsIndustryNotification),
+}
+
+impl NewPossibleEvent {
+    fn into_test(self) -> Self {
+        match self {
+            NewPossibleEvent::Expected(notification)
+            | NewPossibleEvent::Confirmed(notification)
+            | NewPossibleEvent::Cancelled(notification)
+            | NewPossibleEvent::Test(notification) => NewPossibleEvent::Test(notification),
+            NewPossibleEvent::OurSupplierConfirmed => NewPossibleEvent::OurSupplierConfirmed,
+            NewPossibleEvent::Unrecognised(notification) => NewPossibleEvent::Unrecognised(notification),
+        }
+    }
+
+    /// The rnotify component, so that each type of event can be routed differently.
+    fn get_component(&self) -> &'static str {
+        match self {
+            NewPossibleEvent::Expected(_) => "dfs/anticipated",
+            NewPossibleEvent::Confirmed(_) => "dfs/published",
+            NewPossibleEvent::Cancelled(_) => "dfs/cancelled",
+            NewPossibleEvent::OurSupplierConfirmed => "dfs/supplier_confirmed",
+            NewPossibleEvent::Test(_) => "dfs/test",
+            NewPossibleEvent::Unrecognised(_) => "dfs/unrecognised",
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -260,13 +922,17 @@ pub struct DfsIndustryNotificationResponse {
     #[serde(alias = "Notification Issued Time")]
     #[serde(deserialize_with = "naive_time_from_str")]
     time: NaiveTime,
+    #[serde(rename = "Requirement For")]
+    #[serde(default, deserialize_with = "optional_naive_date_from_str")]
+    requirement_for: Option<NaiveDate>,
 }
 
 fn naive_time_from_str<'de, D>(deserializer: D) -> Result<NaiveTime, D::Error>
     where D: Deserializer<'de> {
     let s = String::deserialize(deserializer)?;
-    Ok(NaiveTime::parse_from_str(&s, "%H:%M")
-        .map_err(|err| D::Error::invalid_value(Unexpected::Other(&s), &"HH:MM"))?)
+    NaiveTime::parse_from_str(&s, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(&s, "%H:%M:%S"))
+        .map_err(|_| D::Error::invalid_value(Unexpected::Other(&s), &"HH:MM"))
 }
 fn naive_date_from_str<'de, D>(deserializer: D) -> Result<NaiveDate, D::Error>
     where D: Deserializer<'de> {
@@ -274,12 +940,21 @@ fn naive_date_from_str<'de, D>(deserializer: D) -> Result<NaiveDate, D::Error>
 
     if s.contains("/") {
         NaiveDate::parse_from_str(&s, "%d/%m/%Y")
-            .map_err(|err| D::Error::invalid_value(Unexpected::Other(&s), &"DD/MM/YY"))
+            .map_err(|_| D::Error::invalid_value(Unexpected::Other(&s), &"DD/MM/YY"))
     }
     else {
         NaiveDate::parse_from_str(&s, "%Y-%m-%d")
-            .map_err(|err| D::Error::invalid_value(Unexpected::Other(&s), &"YYYY-MM-DD"))
+            .map_err(|_| D::Error::invalid_value(Unexpected::Other(&s), &"YYYY-MM-DD"))
+    }
+}
+
+fn optional_naive_date_from_str<'de, D>(deserializer: D) -> Result<Option<NaiveDate>, D::Error>
+    where D: Deserializer<'de> {
+    let s = String::deserialize(deserializer)?;
+    if s.trim().is_empty() {
+        return Ok(None);
     }
+    naive_date_from_str(s.into_deserializer()).map(Some)
 }
 
 impl DfsIndustryNotificationResponse {
@@ -287,15 +962,26 @@ impl DfsIndustryNotificationResponse {
         Ok(DfsIndustryNotification::new(
             self.notification_type.parse()?,
             self.date.and_time(self.time),
-            self.description
+            self.description,
+            self.requirement_for,
         ))
     }
 }
 
 #[cfg(test)]
 mod test {
+    use chrono::NaiveDate;
     use csv::Trim;
-    use crate::DfsIndustryNotificationResponse;
+    use std::collections::HashMap;
+    use crate::{DfsIndustryNotificationResponse, NewPossibleEvent, with_requirements, with_supplier_status};
+    use crate::region::Region;
+    use crate::resource::PreviousResources;
+    use crate::resource::anticipation::IndustryNotificationResource;
+    use crate::resource::schema::UTILISATION_REPORT_SCHEMA;
+    use crate::resource::utilisation::{EventOutcome, UtilisationRow};
+    use crate::supplier::SupplierRegistry;
+    use crate::resource::requirement::{ServiceRequirement, ServiceRequirementResource};
+    use crate::webhook::WebhookPayload;
 
     #[test]
     fn test_deserialize() {
@@ -325,6 +1011,7 @@ mod test {
             println!("record: {:?}", record);
             let parsed = record.into_notification().expect("Should be able to parse");
             println!("Parsed: {:?}", parsed);
+            assert_eq!(parsed.get_requirement_for(), &NaiveDate::from_ymd_opt(2023, 12, 15));
         }
     }
 
@@ -342,8 +1029,61 @@ mod test {
         for record in rdr.records() {
             let string_record  = record.expect("Should be able to parse CSV!");
             println!("unparsed record: {:?}", string_record);
-            let row_data: DfsIndustryNotificationResponse = string_record.deserialize(headers.as_ref())
+            let _row_data: DfsIndustryNotificationResponse = string_record.deserialize(headers.as_ref())
                 .expect("Failed to parse csv line!");
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_price_per_region() {
+        let payload = WebhookPayload::sample();
+        let date = payload.get_event_date().expect("Sample should have a date");
+        let s = format!("Delivery Date,From,To,Region,Guaranteed Acceptance Price GBP per MWh
+{date},17:00,18:00,East Midlands,3000
+{date},18:00,19:00,London,2000", date = date.format("%d/%m/%Y"));
+        let mut rdr = csv::ReaderBuilder::new()
+            .trim(Trim::All)
+            .from_reader(s.as_bytes());
+        let requirements: Vec<ServiceRequirement> = rdr.deserialize()
+            .collect::<Result<_, _>>()
+            .expect("Should be able to deserialize");
+        let mut resource = ServiceRequirementResource::default();
+        resource.update(requirements);
+
+        let east_midlands = with_requirements(payload.clone(), &resource, &Region::from_area("East Midlands"));
+        assert_eq!(east_midlands.get_price(), &Some(3000.0));
+        let london = with_requirements(payload.clone(), &resource, &Region::from_area("London"));
+        assert_eq!(london.get_price(), &Some(2000.0));
+        let everywhere = with_requirements(payload, &resource, &None);
+        assert_eq!(everywhere.get_price(), &Some(3000.0));
+    }
+
+    #[test]
+    fn test_supplier_status() {
+        let s = "Notification Issued Date,Notification Issued Time,Requirement For,Requirement Type,Status,Notification Type
+14/12/2023,14:30,15/12/2023,Live,DFS Service Requirement has been published for 15/12/2023. This is System Tagged.,Requirement Published";
+        let mut rdr = csv::Reader::from_reader(s.as_bytes());
+        let record: DfsIndustryNotificationResponse = rdr.deserialize().next()
+            .expect("Should have a row")
+            .expect("Should be able to deserialize");
+        let notification = record.into_notification().expect("Should be able to parse");
+        let payload = WebhookPayload::from_event("test", &NewPossibleEvent::Confirmed(notification), false);
+
+        let s = "Delivery Date,From,To,Registered DFS Participant,DFS Volume MW,Utilisation Price GBP per MWh,Status
+15/12/2023,17:00,17:30,OCTOPUS ENERGY LTD,100,3000,Rejected";
+        let (rows, _) = UTILISATION_REPORT_SCHEMA.read::<UtilisationRow>(s, &HashMap::new())
+            .expect("Should be able to read")
+            .into_parts();
+        let suppliers = SupplierRegistry::new(&[]);
+        let octopus = Some("octopus".to_owned());
+        let date = NaiveDate::from_ymd_opt(2023, 12, 15).unwrap();
+        let mut resources = PreviousResources::create(IndustryNotificationResource::default());
+        resources.record_outcome(EventOutcome::summarise(date, &rows, &octopus, &suppliers).expect("Should have an outcome"));
+
+        let status = |supplier: &Option<String>| serde_json::to_value(with_supplier_status(payload.clone(), &resources, supplier))
+            .expect("Should serialize")["supplier_status"].clone();
+        assert_eq!(status(&octopus), "rejected");
+        // Each supplier only gets its own results.
+        assert!(status(&Some("edf".to_owned())).is_null());
+    }
+}
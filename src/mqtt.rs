@@ -0,0 +1,185 @@
+use std::time::Duration;
+use chrono::{Local, NaiveDateTime, TimeZone};
+use rumqttc::{AsyncClient, ClientError, MqttOptions, QoS};
+use serde::Deserialize;
+use serde_json::json;
+use crate::resource::anticipation::DfsIndustryNotification;
+
+/// Home assistant treats this payload as an unknown state.
+const NONE_PAYLOAD: &str = "None";
+
+fn default_port() -> u16 {
+    1883
+}
+
+fn default_client_id() -> String {
+    "dfs_alert".to_owned()
+}
+
+fn default_topic_prefix() -> String {
+    "dfs".to_owned()
+}
+
+fn default_discovery_prefix() -> String {
+    "homeassistant".to_owned()
+}
+
+/// Configured with a `[mqtt]` table in `dfs_alert.toml`.
+/// For local testing, point this at a mosquitto broker and watch with `mosquitto_sub -v -t 'dfs/#'`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct MqttConfig {
+    host: String,
+    #[serde(default = "default_port")]
+    port: u16,
+    #[serde(default = "default_client_id")]
+    client_id: String,
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    password: Option<String>,
+    #[serde(default = "default_topic_prefix")]
+    topic_prefix: String,
+    /// Set to an empty string to disable home assistant discovery.
+    #[serde(default = "default_discovery_prefix")]
+    discovery_prefix: String,
+}
+
+/// Publishes retained DFS state topics, e.g. `dfs/active`.
+pub struct MqttPublisher {
+    client: AsyncClient,
+    config: MqttConfig,
+}
+
+impl MqttPublisher {
+    /// Connects to the broker, driving the connection on a background task.
+    pub fn connect(config: MqttConfig) -> Self {
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.clone().unwrap_or_default());
+        }
+        let (client, mut event_loop) = AsyncClient::new(options, 20);
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = event_loop.poll().await {
+                    eprintln!("MQTT connection error: {}", err);
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                }
+            }
+        });
+        Self {
+            client,
+            config,
+        }
+    }
+
+    fn topic(&self, suffix: &str) -> String {
+        format!("{}/{}", self.config.topic_prefix, suffix)
+    }
+
+    /// Queues the publish without waiting, as the queue isn't emptied while the broker is down.
+    /// If it is full the publish is dropped, which is fine as the state is published again on every wake up.
+    fn publish_retained<P: Into<Vec<u8>>>(&self, topic: String, payload: P) -> Result<(), ClientError> {
+        self.client.try_publish(topic, QoS::AtLeastOnce, true, payload)
+    }
+
+    /// Publishes home assistant discovery payloads for the next event and active topics.
+    pub async fn publish_discovery(&self) -> Result<(), ClientError> {
+        if self.config.discovery_prefix.is_empty() {
+            return Ok(());
+        }
+        let device = json!({
+            "identifiers": [self.config.client_id],
+            "name": "Demand Flexibility Service",
+            "manufacturer": "dfs_alert",
+        });
+        let id = &self.config.client_id;
+
+        let active = json!({
+            "name": "DFS event active",
+            "unique_id": format!("{}_active", id),
+            "state_topic": self.topic("active"),
+            "payload_on": "true",
+            "payload_off": "false",
+            "device": device,
+        });
+        self.publish_retained(format!("{}/binary_sensor/{}/active/config", self.config.discovery_prefix, id),
+                              active.to_string())?;
+
+        for (key, name) in [("start", "DFS next event start"), ("end", "DFS next event end")] {
+            let sensor = json!({
+                "name": name,
+                "unique_id": format!("{}_next_event_{}", id, key),
+                "state_topic": self.topic(&format!("next_event/{}", key)),
+                "device_class": "timestamp",
+                "device": device,
+            });
+            self.publish_retained(format!("{}/sensor/{}/next_event_{}/config", self.config.discovery_prefix, id, key),
+                                  sensor.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub async fn publish_notification(&self, feed: &str, notification: &DfsIndustryNotification) -> Result<(), ClientError> {
+        let payload = serde_json::to_string(notification)
+            .expect("Notification should always serialize");
+        self.publish_retained(self.topic(&format!("{}/latest_notification", feed_topic(feed))), payload)
+    }
+
+    /// Publishes the next (or current) event window and whether it is happening right now.
+    pub async fn publish_state(&self, next_window: Option<(NaiveDateTime, NaiveDateTime)>, now: &NaiveDateTime) -> Result<(), ClientError> {
+        let (start, end) = match next_window {
+            Some((start, end)) => (to_timestamp(&start), to_timestamp(&end)),
+            None => (NONE_PAYLOAD.to_owned(), NONE_PAYLOAD.to_owned()),
+        };
+        let active = next_window.filter(|(start, end)| start <= now && now < end).is_some();
+        self.publish_retained(self.topic("next_event/start"), start)?;
+        self.publish_retained(self.topic("next_event/end"), end)?;
+        self.publish_retained(self.topic("active"), active.to_string())
+    }
+}
+
+fn to_timestamp(time: &NaiveDateTime) -> String {
+    Local.from_local_datetime(time)
+        .earliest()
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_else(|| NONE_PAYLOAD.to_owned())
+}
+
+/// Turns a feed name like "live (old)" into a topic level like "live_old".
+fn feed_topic(feed: &str) -> String {
+    feed.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.to_ascii_lowercase())
+        .collect::<Vec<String>>()
+        .join("_")
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use chrono::NaiveDate;
+    use crate::mqtt::{feed_topic, MqttConfig, MqttPublisher};
+
+    #[test]
+    fn test_feed_topic() {
+        assert_eq!(feed_topic("live (old)"), "live_old");
+        assert_eq!(feed_topic("new always live"), "new_always_live");
+    }
+
+    #[tokio::test]
+    async fn test_broker_down() {
+        // Nothing listens on the discard port, so the queue is never emptied.
+        let config: MqttConfig = toml::from_str("host = \"127.0.0.1\"\nport = 9").unwrap();
+        let publisher = MqttPublisher::connect(config);
+        let now = NaiveDate::from_ymd_opt(2023, 12, 15).unwrap().and_hms_opt(17, 0, 0).unwrap();
+        let results = tokio::time::timeout(Duration::from_secs(5), async {
+            let mut results = vec![];
+            for _ in 0..20 {
+                results.push(publisher.publish_state(None, &now).await);
+            }
+            results
+        }).await.expect("Publishing shouldn't wait for the broker");
+        assert!(results.iter().any(Result::is_err));
+    }
+}
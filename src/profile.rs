@@ -0,0 +1,213 @@
+use std::error::Error;
+use chrono::NaiveDate;
+use rnotifylib::config::Config;
+use rnotifylib::message_router::MessageRouter;
+use serde::Deserialize;
+use crate::config::LevelConfig;
+use crate::OUR_SUPPLIER;
+use crate::quiet::{QuietHoursConfig, QuietRouter};
+use crate::region::{Region, RegionConfig};
+use crate::resource::requirement::ServiceRequirementResource;
+use crate::reward::RewardConfig;
+use crate::supplier::SupplierRegistry;
+
+fn default_supplier() -> String {
+    OUR_SUPPLIER.to_owned()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// A household that gets its own event messages, configured with a `[[profile]]` table in `dfs_alert.toml`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ProfileConfig {
+    name: String,
+    #[serde(default = "default_supplier")]
+    supplier: String,
+    #[serde(default)]
+    region: Option<RegionConfig>,
+    /// An rnotify routing file for this profile's messages, otherwise `routing.toml` is used.
+    #[serde(default)]
+    routing: Option<String>,
+    /// Otherwise the top level `[levels]` are used.
+    #[serde(default)]
+    levels: Option<LevelConfig>,
+    #[serde(default = "default_true")]
+    include_test: bool,
+    /// Otherwise the top level `[reward]` is used.
+    #[serde(default)]
+    reward: Option<RewardConfig>,
+    /// Only used with `routing`, otherwise the top level `[[quiet_hours]]` apply.
+    #[serde(default)]
+    quiet_hours: Option<Vec<QuietHoursConfig>>,
+}
+
+fn identify_supplier(name: &str, suppliers: &SupplierRegistry) -> Option<String> {
+    let id = suppliers.identify(name);
+    if id.is_none() {
+        eprintln!("Supplier '{}' isn't known, add it as a [[supplier]] in the config", name);
+    }
+    id.map(str::to_owned)
+}
+
+pub struct Profile {
+    name: Option<String>,
+    /// The canonical supplier id, if the configured name is known.
+    supplier: Option<String>,
+    region: Option<Region>,
+    router: Option<QuietRouter>,
+    levels: LevelConfig,
+    include_test: bool,
+    reward: Option<RewardConfig>,
+}
+
+impl Profile {
+    /// The profile used when none are configured, which behaves as dfs_alert always has.
+    pub fn default_profile(levels: &LevelConfig, reward: &Option<RewardConfig>, region: &Option<Region>, suppliers: &SupplierRegistry) -> Self {
+        Self {
+            name: None,
+            supplier: identify_supplier(OUR_SUPPLIER, suppliers),
+            region: region.clone(),
+            router: None,
+            levels: levels.clone(),
+            include_test: true,
+            reward: reward.clone(),
+        }
+    }
+
+    /// The top level `[region]` is used if the profile doesn't have its own.
+    pub async fn load(config: &ProfileConfig, default_levels: &LevelConfig, default_reward: &Option<RewardConfig>, default_region: &Option<Region>,
+                      default_quiet_hours: &[QuietHoursConfig], suppliers: &SupplierRegistry) -> Result<Self, Box<dyn Error>> {
+        let router = match &config.routing {
+            Some(path) => {
+                let routing_config: Config = toml::from_str(&std::fs::read_to_string(path)?)?;
+                let quiet_hours = config.quiet_hours.clone().unwrap_or_else(|| default_quiet_hours.to_vec());
+                Some(QuietRouter::new(MessageRouter::from_config(routing_config), quiet_hours))
+            }
+            None => None,
+        };
+        let region = match &config.region {
+            Some(region_config) => Some(Region::resolve(region_config).await?),
+            None => default_region.clone(),
+        };
+        Ok(Self {
+            name: Some(config.name.clone()),
+            supplier: identify_supplier(&config.supplier, suppliers),
+            region,
+            router,
+            levels: config.levels.clone().unwrap_or_else(|| default_levels.clone()),
+            include_test: config.include_test,
+            reward: config.reward.clone().or_else(|| default_reward.clone()),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let region = self.region.as_ref().map(Region::get_name).unwrap_or("all regions");
+        let supplier = self.supplier.as_deref().unwrap_or("unknown supplier");
+        format!("{} ({}, {})", self.name.as_deref().unwrap_or("default"), supplier, region)
+    }
+
+    pub fn get_name(&self) -> &Option<String> {
+        &self.name
+    }
+
+    pub fn get_supplier(&self) -> &Option<String> {
+        &self.supplier
+    }
+
+    pub fn get_region(&self) -> &Option<Region> {
+        &self.region
+    }
+
+    pub fn get_reward(&self) -> &Option<RewardConfig> {
+        &self.reward
+    }
+
+    pub fn get_levels(&self) -> &LevelConfig {
+        &self.levels
+    }
+
+    /// The profile's own router, or the default one from `routing.toml`.
+    pub fn get_router<'a>(&'a self, default: &'a QuietRouter) -> &'a QuietRouter {
+        self.router.as_ref().unwrap_or(default)
+    }
+
+    /// The router from the profile's own `routing`, if it has one.
+    pub fn get_own_router(&self) -> &Option<QuietRouter> {
+        &self.router
+    }
+
+    /// The name of the feed in this profile's messages, so relatives can tell whose they are.
+    pub fn label(&self, feed: &str) -> String {
+        match &self.name {
+            Some(name) => format!("{} for {}", feed, name),
+            None => feed.to_owned(),
+        }
+    }
+
+    /// Whether this profile should be told about the event, `test` if it is a test notification or from a test feed.
+    /// If the requirements for the date are known, at least one of them has to be for the profile's region.
+    pub fn wants(&self, test: bool, date: &Option<NaiveDate>, requirements: &ServiceRequirementResource) -> bool {
+        if test && !self.include_test {
+            return false;
+        }
+        match date {
+            Some(date) => self.wants_areas(&requirements.get_areas(date)),
+            None => true,
+        }
+    }
+
+    /// Whether this profile should be told about an event with requirements for the given areas, see [`Region::applies_to_any`].
+    pub fn wants_areas(&self, areas: &[Option<String>]) -> bool {
+        Region::applies_to_any(&self.region, areas)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use csv::Trim;
+        use crate::profile::{Profile, ProfileConfig};
+    use crate::region::Region;
+    use crate::resource::requirement::{ServiceRequirement, ServiceRequirementResource};
+    use crate::supplier::SupplierRegistry;
+
+    #[tokio::test]
+    async fn test_wants_and_label() {
+        let config: ProfileConfig = toml::from_str("name = \"Gran\"\ninclude_test = false\n[region]\ngsp_group = \"_B\"").unwrap();
+        let levels = toml::from_str("").unwrap();
+        let suppliers = SupplierRegistry::new(&[]);
+        let profile = Profile::load(&config, &levels, &None, &None, &[], &suppliers).await
+            .expect("Should load without looking anything up");
+        assert_eq!(profile.label("live"), "live for Gran");
+        let default = Profile::default_profile(&levels, &None, &None, &suppliers);
+        assert_eq!(default.label("live"), "live");
+
+        let s = "Delivery Date,From,To,Service Requirement MW,Region
+15/12/2023,17:00,17:30,400,Eastern
+16/12/2023,17:00,17:30,400,
+17/12/2023,17:00,17:30,400,East Midlands";
+        let mut rdr = csv::ReaderBuilder::new()
+            .trim(Trim::All)
+            .from_reader(s.as_bytes());
+        let mut requirements = ServiceRequirementResource::default();
+        requirements.update(rdr.deserialize::<ServiceRequirement>()
+            .collect::<Result<_, _>>()
+            .expect("Should be able to deserialize"));
+
+        let date = |day| Some(NaiveDate::from_ymd_opt(2023, 12, day).unwrap());
+        assert!(!profile.wants(false, &date(15), &requirements));
+        assert!(profile.wants(false, &date(16), &requirements));
+        assert!(profile.wants(false, &date(17), &requirements));
+        // Nothing is known about the date yet.
+        assert!(profile.wants(false, &date(18), &requirements));
+        assert!(default.wants(false, &date(15), &requirements));
+
+        assert!(!profile.wants(true, &date(17), &requirements));
+        assert!(default.wants(true, &date(17), &requirements));
+
+        let region = Region::resolve(&toml::from_str("gsp_group = \"_B\"").unwrap()).await.unwrap();
+        assert_eq!(requirements.in_region(&Some(region)).get_last_data().len(), 2);
+    }
+}
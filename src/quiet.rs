@@ -0,0 +1,363 @@
+use std::cell::{Cell, RefCell};
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+use rnotifylib::message::{Level, Message, MessageDetail};
+use rnotifylib::message::author::Author;
+use rnotifylib::message::component::Component;
+use rnotifylib::message::formatted_detail::{FormattedMessageComponent, FormattedMessageDetail, FormattedString, Style};
+use rnotifylib::message_router::MessageRouter;
+use serde::{Deserialize, Serialize};
+
+/// A time of day when some messages are held back, configured with a `[[quiet_hours]]` table.
+/// `end` may be before `start` to wrap past midnight, e.g. 22:00 to 07:00.
+#[derive(Deserialize, Debug, Clone)]
+pub struct QuietHoursConfig {
+    start: NaiveTime,
+    end: NaiveTime,
+    /// The levels to hold back, or all of them if empty.
+    #[serde(default)]
+    levels: Vec<Level>,
+    /// The components to hold back, including their children, e.g. "dfs/test". All of them if empty.
+    #[serde(default)]
+    components: Vec<String>,
+}
+
+impl QuietHoursConfig {
+    fn is_quiet(&self, time: &NaiveTime) -> bool {
+        if self.start <= self.end {
+            &self.start <= time && time < &self.end
+        } else {
+            time >= &self.start || time < &self.end
+        }
+    }
+
+    fn applies_to(&self, message: &Message) -> bool {
+        let level = self.levels.is_empty() || self.levels.contains(message.get_level());
+        let component = self.components.is_empty() || message.get_component().as_ref()
+            .map(|component| component.to_string())
+            .map(|component| self.components.iter().any(|prefix| component == *prefix || component.starts_with(&format!("{}/", prefix))))
+            .unwrap_or(false);
+        level && component
+    }
+
+    /// When these quiet hours next end.
+    fn next_end(&self, now: &NaiveDateTime) -> NaiveDateTime {
+        let end = now.date().and_time(self.end);
+        if &end <= now { end + Duration::days(1) } else { end }
+    }
+}
+
+/// A message held back until quiet hours end.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueuedMessage {
+    #[serde(with = "saved_message")]
+    message: Message,
+    until: NaiveDateTime,
+}
+
+/// rnotify's [`Message`] can't be serialized, so queued messages are saved as the parts dfs_alert builds them from.
+/// Every dfs_alert message has the same author and plain text, so those aren't kept.
+mod saved_message {
+    use rnotifylib::message::{Level, Message, MessageDetail};
+    use rnotifylib::message::author::Author;
+    use rnotifylib::message::component::Component;
+    use rnotifylib::message::formatted_detail::{FormattedMessageComponent, FormattedMessageDetail, FormattedString};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct SavedMessage {
+        level: Level,
+        title: Option<String>,
+        component: Option<Component>,
+        unix_timestamp_millis: i64,
+        raw: String,
+        /// Each text block, or section with its name, or `None` if the message only has raw text.
+        blocks: Option<Vec<(Option<String>, Vec<String>)>>,
+    }
+
+    fn strings(contents: &[FormattedString]) -> Vec<String> {
+        contents.iter().map(|s| s.get_string().to_owned()).collect()
+    }
+
+    pub fn serialize<S: Serializer>(message: &Message, serializer: S) -> Result<S::Ok, S::Error> {
+        let detail = message.get_message_detail();
+        let blocks = match detail {
+            MessageDetail::Raw(_) => None,
+            MessageDetail::Formatted(formatted) => Some(formatted.components().iter()
+                .map(|component| match component {
+                    FormattedMessageComponent::Section(name, contents) => (Some(name.clone()), strings(contents)),
+                    FormattedMessageComponent::Text(contents) => (None, strings(contents)),
+                })
+                .collect()),
+        };
+        SavedMessage {
+            level: message.get_level().clone(),
+            title: message.get_title().clone(),
+            component: message.get_component().clone(),
+            unix_timestamp_millis: message.get_unix_timestamp_millis(),
+            raw: detail.raw().to_owned(),
+            blocks,
+        }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Message, D::Error> {
+        let saved = SavedMessage::deserialize(deserializer)?;
+        let detail = match saved.blocks {
+            None => MessageDetail::Raw(saved.raw),
+            Some(blocks) => {
+                let components = blocks.into_iter()
+                    .map(|(name, contents)| {
+                        let contents = contents.into_iter().map(FormattedString::plain).collect();
+                        match name {
+                            Some(name) => FormattedMessageComponent::Section(name, contents),
+                            None => FormattedMessageComponent::Text(contents),
+                        }
+                    })
+                    .collect();
+                MessageDetail::Formatted(FormattedMessageDetail::new(saved.raw, components))
+            }
+        };
+        Ok(Message::new(saved.level, saved.title, detail, saved.component, Author::parse("dfs_alert".to_owned()), saved.unix_timestamp_millis))
+    }
+}
+
+/// Wraps a [`MessageRouter`], holding back messages during quiet hours and sending them together once they end.
+pub struct QuietRouter {
+    router: MessageRouter,
+    rules: Vec<QuietHoursConfig>,
+    queue: RefCell<Vec<QueuedMessage>>,
+    changed: Cell<bool>,
+}
+
+impl QuietRouter {
+    pub fn new(router: MessageRouter, rules: Vec<QuietHoursConfig>) -> Self {
+        Self {
+            router,
+            rules,
+            queue: RefCell::new(vec![]),
+            changed: Cell::new(false),
+        }
+    }
+
+    /// Routes the message, unless it is quiet hours for it, in which case it is queued and no destinations are informed.
+    pub fn route(&self, message: &Message) -> Result<usize, String> {
+        self.route_at(message, &chrono::Local::now().naive_local())
+    }
+
+    fn route_at(&self, message: &Message, now: &NaiveDateTime) -> Result<usize, String> {
+        if let Some(until) = self.quiet_until(message, now) {
+            println!("Holding {:?} back until quiet hours end at {}", message.get_title(), until);
+            self.queue.borrow_mut().push(QueuedMessage { message: message.clone(), until });
+            self.changed.set(true);
+            return Ok(0);
+        }
+        self.router.route(message).map_err(|err| err.to_string())
+    }
+
+    /// Routes the message straight away, even in quiet hours, for messages that would be stale or lost if held back.
+    pub fn route_now(&self, message: &Message) -> Result<usize, String> {
+        self.router.route(message).map_err(|err| err.to_string())
+    }
+
+    /// When the message can be sent, if it is quiet hours for it now.
+    fn quiet_until(&self, message: &Message, now: &NaiveDateTime) -> Option<NaiveDateTime> {
+        self.rules.iter()
+            .filter(|rule| rule.applies_to(message) && rule.is_quiet(&now.time()))
+            .map(|rule| rule.next_end(now))
+            .max()
+    }
+
+    /// Sends every queued message whose quiet hours have ended, returning whether any were.
+    /// Messages with the same component are combined, so they still go wherever that component is routed.
+    pub fn release_due(&self, now: &NaiveDateTime) -> bool {
+        let due: Vec<QueuedMessage> = {
+            let mut queue = self.queue.borrow_mut();
+            let (due, waiting) = queue.drain(..).partition(|queued| &queued.until <= now);
+            *queue = waiting;
+            due
+        };
+        if due.is_empty() {
+            return false;
+        }
+        println!("Quiet hours over, sending {} held back messages", due.len());
+        let mut groups: Vec<(Option<Component>, Vec<Message>)> = vec![];
+        for queued in due {
+            match groups.iter_mut().find(|(component, _)| component == queued.message.get_component()) {
+                Some((_, messages)) => messages.push(queued.message),
+                None => groups.push((queued.message.get_component().clone(), vec![queued.message])),
+            }
+        }
+        for (_, messages) in groups {
+            let message = match messages.as_slice() {
+                [message] => message.clone(),
+                _ => combine_messages(&messages),
+            };
+            match self.router.route(&message) {
+                Ok(amt) => {
+                    println!("Informed {} destinations of held back {:?}", amt, message.get_title());
+                }
+                Err(send_errors) => {
+                    eprintln!("Errors informing some destinations of held back messages:");
+                    eprintln!("{}", send_errors);
+                }
+            }
+        }
+        true
+    }
+
+    /// When the next queued message can be sent.
+    pub fn next_release(&self) -> Option<NaiveDateTime> {
+        self.queue.borrow().iter().map(|queued| queued.until).min()
+    }
+
+    /// Whether anything has been queued since this was last called.
+    pub fn take_changed(&self) -> bool {
+        self.changed.replace(false)
+    }
+
+    pub fn get_queue(&self) -> Vec<QueuedMessage> {
+        self.queue.borrow().clone()
+    }
+
+    pub fn restore(&self, queue: Vec<QueuedMessage>) {
+        *self.queue.borrow_mut() = queue;
+    }
+}
+
+/// Puts messages with the same component into one, each with its title and whole body, at the level of the most serious one.
+fn combine_messages(messages: &[Message]) -> Message {
+    let level = messages.iter()
+        .map(Message::get_level)
+        .max()
+        .cloned()
+        .unwrap_or_default();
+    let mut raw = vec![];
+    let mut components = vec![];
+    for message in messages {
+        let title = message.get_title().clone().unwrap_or_else(|| "Untitled message".to_owned());
+        let detail = message.get_message_detail();
+        raw.push(format!("{}\n{}", title, detail.raw()));
+        components.push(FormattedMessageComponent::Text(vec![FormattedString::styled(format!("{}\n", title), Style::Bold)]));
+        match detail {
+            MessageDetail::Raw(raw) => components.push(FormattedMessageComponent::Text(vec![FormattedString::plain(raw)])),
+            MessageDetail::Formatted(formatted) => components.extend(formatted.components().iter().cloned()),
+        }
+    }
+    let detail = MessageDetail::Formatted(FormattedMessageDetail::new(raw.join("\n\n"), components));
+    let newest = messages.iter().map(Message::get_unix_timestamp_millis).max().unwrap_or_default();
+    Message::new(level, Some(format!("{} messages held back during quiet hours", messages.len())), detail,
+                 messages[0].get_component().clone(), Author::parse("dfs_alert".to_owned()), newest)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc::channel;
+    use chrono::{NaiveDate, NaiveTime};
+    use rnotifylib::destination::kinds::rust_receiver::RustReceiverDestination;
+    use rnotifylib::destination::routed_destination::RoutedDestinationBase;
+    use rnotifylib::message::builder::MessageBuilder;
+    use rnotifylib::message::detail_builder::FormattedStringAppendable;
+    use rnotifylib::message::Level;
+    use rnotifylib::message_router::{MessageRouter, RoutingInfo};
+    use crate::quiet::{QuietHoursConfig, QuietRouter};
+
+    #[test]
+    fn test_overnight_quiet_hours() {
+        let rule: QuietHoursConfig = toml::from_str(r#"
+            start = "22:00:00"
+            end = "07:00:00"
+            components = ["dfs/test"]
+        "#).unwrap();
+        let time = |h| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+        assert!(rule.is_quiet(&time(23)));
+        assert!(rule.is_quiet(&time(3)));
+        assert!(!rule.is_quiet(&time(10)));
+
+        let night = NaiveDate::from_ymd_opt(2023, 12, 15).unwrap().and_time(time(23));
+        assert_eq!(rule.next_end(&night), NaiveDate::from_ymd_opt(2023, 12, 16).unwrap().and_time(time(7)));
+
+        let message = |component: &str| {
+            let mut builder = MessageBuilder::new();
+            builder.level(Level::Info).component(component.into());
+            builder.build()
+        };
+        assert!(rule.applies_to(&message("dfs/test")));
+        assert!(rule.applies_to(&message("dfs/test/new")));
+        assert!(!rule.applies_to(&message("dfs/testing")));
+        assert!(!rule.applies_to(&message("dfs/published")));
+    }
+
+    #[test]
+    fn test_queue_and_release() {
+        let rule: QuietHoursConfig = toml::from_str(r#"
+            start = "22:00:00"
+            end = "07:00:00"
+        "#).unwrap();
+        // A destination for events and one for errors, as someone might route them.
+        let (published_tx, published) = channel();
+        let (errors_tx, errors) = channel();
+        let router = || {
+            let mut router = MessageRouter::empty();
+            for (id, component, tx) in [("published", "dfs/published", published_tx.clone()), ("errors", "dfs_alert/error", errors_tx.clone())] {
+                let routing: RoutingInfo = toml::from_str(&format!("whitelist = [{{ component = \"{}\" }}]", component)).unwrap();
+                router.add_destination(Box::new(RoutedDestinationBase::create(id.to_owned(), RustReceiverDestination::create(tx), routing)));
+            }
+            router
+        };
+        let message = |level: Level, title: &str, component: &str, body: &str| {
+            let mut builder = MessageBuilder::new();
+            builder.level(level)
+                .title(title)
+                .component(component.into())
+                .author("dfs_alert")
+                .body(|detail| {
+                    detail.raw(body.to_owned());
+                    detail.section("Details", |section| {
+                        section.append_plain(body);
+                    });
+                });
+            builder.build()
+        };
+        let first = message(Level::Info, "First", "dfs/published", "17:30 - 18:30");
+        let second = message(Level::Warn, "Second", "dfs/published", "£3000/MWh");
+        let error = message(Level::Error, "Error", "dfs_alert/error", "Not a success!");
+
+        let quiet = QuietRouter::new(router(), vec![rule.clone()]);
+        let date = NaiveDate::from_ymd_opt(2023, 12, 15).unwrap();
+        let night = date.and_hms_opt(23, 0, 0).unwrap();
+        let morning = date.succ_opt().unwrap().and_hms_opt(7, 0, 0).unwrap();
+        for message in [&first, &second, &error] {
+            assert_eq!(quiet.route_at(message, &night), Ok(0));
+        }
+        assert!(quiet.take_changed());
+        assert!(!quiet.take_changed());
+        assert!(published.try_recv().is_err());
+        // Service messages aren't held back.
+        assert_eq!(quiet.route_now(&message(Level::Info, "Stopping", "dfs/published", "")), Ok(1));
+        assert_eq!(published.try_recv().unwrap().get_title(), &Some("Stopping".to_owned()));
+        assert_eq!(quiet.get_queue().len(), 3);
+        assert_eq!(quiet.next_release(), Some(morning));
+
+        // Survives being saved and loaded.
+        let restored = QuietRouter::new(router(), vec![rule]);
+        let queue = serde_json::to_string(&quiet.get_queue()).unwrap();
+        restored.restore(serde_json::from_str(&queue).unwrap());
+        assert_eq!(restored.get_queue()[0].message, first);
+        assert_eq!(restored.next_release(), Some(morning));
+
+        assert!(!restored.release_due(&(morning - chrono::Duration::minutes(1))));
+        assert!(restored.release_due(&morning));
+        assert!(restored.get_queue().is_empty());
+        assert_eq!(restored.next_release(), None);
+        assert!(!restored.release_due(&morning));
+
+        // The events are combined, but still go where events go, with their bodies.
+        let combined = published.try_recv().unwrap();
+        assert!(published.try_recv().is_err());
+        assert_eq!(combined.get_title(), &Some("2 messages held back during quiet hours".to_owned()));
+        assert_eq!(combined.get_level(), &Level::Warn);
+        assert_eq!(combined.get_message_detail().raw(), "First\n17:30 - 18:30\n\nSecond\n£3000/MWh");
+        // A message on its own is sent as it was.
+        assert_eq!(errors.try_recv().unwrap(), error);
+        assert!(errors.try_recv().is_err());
+    }
+}
@@ -0,0 +1,42 @@
+use std::cell::Cell;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Spaces out requests to the ESO data portal. Shared by every feed, so that polling them together doesn't hammer it.
+/// Only for futures polled on the same task, e.g. with `join_all`, as it isn't `Sync`.
+pub struct RateLimiter {
+    interval: Duration,
+    next: Cell<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Cell::new(Instant::now()),
+        }
+    }
+
+    /// Waits until it is this request's turn.
+    pub async fn wait(&self) {
+        // The slot is taken before waiting, so requests go in the order they asked.
+        let slot = self.next.get().max(Instant::now());
+        self.next.set(slot + self.interval);
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use tokio::time::Instant;
+    use crate::rate_limit::RateLimiter;
+
+    #[tokio::test]
+    async fn test_requests_spaced_out() {
+        let limiter = RateLimiter::new(Duration::from_millis(20));
+        let start = Instant::now();
+        futures::future::join_all((0..3).map(|_| limiter.wait())).await;
+        assert!(start.elapsed() >= Duration::from_millis(40), "{:?}", start.elapsed());
+    }
+}
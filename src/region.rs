@@ -0,0 +1,156 @@
+use std::error::Error;
+use serde::Deserialize;
+use crate::OUR_REGION;
+
+const GSP_LOOKUP_URL: &str = "https://api.octopus.energy/v1/industry/grid-supply-points/";
+
+/// The GSP groups, with the name of the DNO region each covers.
+const GSP_GROUPS: &[(&str, &str)] = &[
+    ("_A", "Eastern"),
+    ("_B", "East Midlands"),
+    ("_C", "London"),
+    ("_D", "Merseyside and North Wales"),
+    ("_E", "West Midlands"),
+    ("_F", "North Eastern"),
+    ("_G", "North Western"),
+    ("_H", "Southern"),
+    ("_J", "South Eastern"),
+    ("_K", "South Wales"),
+    ("_L", "South Western"),
+    ("_M", "Yorkshire"),
+    ("_N", "South Scotland"),
+    ("_P", "North Scotland"),
+];
+
+/// Areas that mean a requirement applies everywhere.
+const NATIONAL: &[&str] = &["", "national", "all", "gb", "great britain"];
+
+fn default_name() -> String {
+    OUR_REGION.to_owned()
+}
+
+/// Configured with a `[region]` table in `dfs_alert.toml`.
+/// The GSP group is used if given, then the postcode, then the region name.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RegionConfig {
+    #[serde(default = "default_name")]
+    name: String,
+    #[serde(default)]
+    gsp_group: Option<String>,
+    #[serde(default)]
+    postcode: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GspLookupResult {
+    results: Vec<GspLookup>,
+}
+
+#[derive(Deserialize)]
+struct GspLookup {
+    group_id: String,
+}
+
+fn normalise(area: &str) -> String {
+    area.trim().trim_start_matches('_').to_lowercase()
+}
+
+/// The GSP group that the household is in, which regional requirements are filtered by.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    gsp_group: &'static str,
+    name: &'static str,
+}
+
+impl Region {
+    pub fn from_area(area: &str) -> Option<Self> {
+        let area = normalise(area);
+        GSP_GROUPS.iter()
+            .find(|(group, name)| normalise(group) == area || normalise(name) == area)
+            .map(|(gsp_group, name)| Self {
+                gsp_group,
+                name,
+            })
+    }
+
+    /// Works out the region from the config, looking up the postcode if there is one.
+    pub async fn resolve(config: &RegionConfig) -> Result<Self, Box<dyn Error>> {
+        if let Some(gsp_group) = &config.gsp_group {
+            return Self::from_area(gsp_group)
+                .ok_or_else(|| format!("Unknown GSP group '{}'", gsp_group).into());
+        }
+        if let Some(postcode) = &config.postcode {
+            let result: GspLookupResult = reqwest::Client::new().get(GSP_LOOKUP_URL)
+                .query(&[("postcode", postcode)])
+                .send().await?
+                .json().await?;
+            let group_id = &result.results.first()
+                .ok_or_else(|| format!("No GSP group found for postcode '{}'", postcode))?
+                .group_id;
+            return Self::from_area(group_id)
+                .ok_or_else(|| format!("Unknown GSP group '{}' for postcode '{}'", group_id, postcode).into());
+        }
+        Self::from_area(&config.name)
+            .ok_or_else(|| format!("Unknown region '{}'", config.name).into())
+    }
+
+    pub fn get_name(&self) -> &str {
+        self.name
+    }
+
+    /// Whether a requirement for the given area applies to this region.
+    /// The area can be a GSP group or region name, a list of them, or blank for a national requirement.
+    /// Areas that aren't recognised, e.g. a new zone, apply so that events aren't missed.
+    pub fn applies_to(&self, area: &str) -> bool {
+        if NATIONAL.contains(&normalise(area).as_str()) {
+            return true;
+        }
+        let mut regions = vec![];
+        for part in area.split([',', ';', '/']) {
+            match Region::from_area(part) {
+                Some(region) => regions.push(region),
+                None => {
+                    eprintln!("Unrecognised area '{}' in requirement, assuming it includes {}", part.trim(), self.name);
+                    return true;
+                }
+            }
+        }
+        regions.contains(self)
+    }
+
+    /// Whether an event with requirements for the given areas applies, to anywhere if there is no region.
+    /// Requirements without an area, or no requirements at all, apply everywhere.
+    pub fn applies_to_any(region: &Option<Region>, areas: &[Option<String>]) -> bool {
+        let region = match region {
+            Some(region) => region,
+            None => return true,
+        };
+        areas.is_empty() || areas.iter().any(|area| area.as_ref()
+            .map(|area| region.applies_to(area))
+            .unwrap_or(true))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::region::Region;
+
+    #[test]
+    fn test_applies_to() {
+        let region = Region::from_area("East Midlands").expect("Should be a region");
+        assert_eq!(Region::from_area("_B"), Some(region.clone()));
+        assert!(region.applies_to(""));
+        assert!(region.applies_to("National"));
+        assert!(region.applies_to("B"));
+        assert!(region.applies_to("Eastern; east midlands"));
+        assert!(!region.applies_to("_A"));
+        assert!(!region.applies_to("Eastern, London"));
+    }
+
+    #[test]
+    fn test_unknown_area_applies() {
+        let region = Region::from_area("East Midlands").expect("Should be a region");
+        assert!(region.applies_to("Somewhere new"));
+        assert!(region.applies_to("Eastern; Midlands Zone 2"));
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashSet;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use rnotifylib::message::{Level, Message};
+use rnotifylib::message::builder::MessageBuilder;
+use rnotifylib::message::component::Component;
+use rnotifylib::message::detail_builder::FormattedStringAppendable;
+use serde::{Deserialize, Serialize};
+use crate::resource::anticipation::{DfsIndustryNotification, IndustryNotificationType};
+
+/// How late a reminder can be sent, e.g. if the process was down when it was due.
+const MAX_LATENESS_MINUTES: i64 = 30;
+/// How long to remember sent reminders and cancellations for.
+const FORGET_AFTER_DAYS: i64 = 7;
+
+fn default_minutes_before() -> Vec<u32> {
+    vec![60, 10]
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Configured with a `[reminders]` table in `dfs_alert.toml`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReminderConfig {
+    #[serde(default = "default_minutes_before")]
+    minutes_before: Vec<u32>,
+    #[serde(default = "default_true")]
+    at_start: bool,
+    #[serde(default = "default_true")]
+    at_end: bool,
+}
+
+impl Default for ReminderConfig {
+    fn default() -> Self {
+        Self {
+            minutes_before: default_minutes_before(),
+            at_start: true,
+            at_end: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ReminderKind {
+    Before(u32),
+    Start,
+    End,
+}
+
+impl ReminderKind {
+    fn get_component(&self) -> &'static str {
+        match self {
+            ReminderKind::Before(_) => "dfs/reminder/before",
+            ReminderKind::Start => "dfs/reminder/start",
+            ReminderKind::End => "dfs/reminder/end",
+        }
+    }
+}
+
+/// An event window from one feed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventWindow {
+    feed: String,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    /// The area of each requirement in the window, for working out who it applies to.
+    areas: Vec<Option<String>>,
+}
+
+impl EventWindow {
+    pub fn new(feed: &str, start: NaiveDateTime, end: NaiveDateTime) -> Self {
+        Self {
+            feed: feed.to_owned(),
+            start,
+            end,
+            areas: vec![],
+        }
+    }
+
+    pub fn with_areas(mut self, areas: Vec<Option<String>>) -> Self {
+        self.areas = areas;
+        self
+    }
+
+    pub fn get_areas(&self) -> &Vec<Option<String>> {
+        &self.areas
+    }
+
+    pub fn get_feed(&self) -> &str {
+        &self.feed
+    }
+
+    pub fn get_start(&self) -> &NaiveDateTime {
+        &self.start
+    }
+
+    pub fn get_end(&self) -> &NaiveDateTime {
+        &self.end
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Reminder {
+    feed: String,
+    kind: ReminderKind,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+}
+
+impl Reminder {
+    /// The same reminder for the same window, whichever feed it is from.
+    fn is_same_time(&self, other: &Reminder) -> bool {
+        self.kind == other.kind && self.start == other.start
+    }
+
+    fn get_due(&self) -> NaiveDateTime {
+        match self.kind {
+            ReminderKind::Before(minutes) => self.start - Duration::minutes(minutes as i64),
+            ReminderKind::Start => self.start,
+            ReminderKind::End => self.end,
+        }
+    }
+
+    pub fn create_message(&self) -> Message {
+        let title = match self.kind {
+            ReminderKind::Before(minutes) => format!("DFS event starts in {} minutes", minutes),
+            ReminderKind::Start => "DFS event starting now".to_owned(),
+            ReminderKind::End => "DFS event finished".to_owned(),
+        };
+        let window = format!("{} - {} on {}", self.start.format("%H:%M"), self.end.format("%H:%M"), self.start.format("%a %d %b"));
+
+        let mut message_builder = MessageBuilder::new();
+        message_builder
+            .level(Level::Info)
+            .title(&title)
+            .body(|body| {
+                body.raw(format!("{} ({}) - {}", title, window, self.feed));
+                body.text_block(|block| {
+                    block.append_plain(format!("Event window: {} ({})", window, self.feed));
+                });
+            })
+            .component(Component::from(self.kind.get_component()))
+            .author("dfs_alert");
+        message_builder.build()
+    }
+}
+
+/// Which reminders have been sent and which event dates were cancelled, kept in saved state.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ReminderState {
+    #[serde(default)]
+    sent: HashSet<Reminder>,
+    #[serde(default)]
+    cancelled: HashSet<(String, NaiveDate)>,
+}
+
+impl ReminderState {
+    /// Records a cancellation from the latest notification of a feed, returning whether it was new.
+    pub fn observe(&mut self, feed: &str, notification: &DfsIndustryNotification) -> bool {
+        if notification.get_type() != &IndustryNotificationType::RequirementCancelled {
+            return false;
+        }
+        let date = notification.get_event_date()
+            .unwrap_or_else(|| notification.get_when().date());
+        self.cancelled.insert((feed.to_owned(), date))
+    }
+
+    /// Every reminder that hasn't been sent, with the window it is for.
+    /// Feeds can share a window, e.g. the 2022 live feed and the 2023 one, but it is only one reminder.
+    fn all_reminders<'a>(&self, config: &ReminderConfig, windows: &'a [EventWindow]) -> Vec<(Reminder, &'a EventWindow)> {
+        let mut kinds: Vec<ReminderKind> = config.minutes_before.iter()
+            .map(|minutes| ReminderKind::Before(*minutes))
+            .collect();
+        if config.at_start {
+            kinds.push(ReminderKind::Start);
+        }
+        if config.at_end {
+            kinds.push(ReminderKind::End);
+        }
+
+        let mut reminders: Vec<(Reminder, &'a EventWindow)> = vec![];
+        let unsent = windows.iter()
+            .filter(|window| !self.cancelled.contains(&(window.feed.clone(), window.start.date())))
+            .flat_map(|window| kinds.iter().map(move |kind| (Reminder {
+                feed: window.feed.clone(),
+                kind: kind.clone(),
+                start: window.start,
+                end: window.end,
+            }, window)))
+            .filter(|(reminder, _)| !self.sent.iter().any(|sent| sent.is_same_time(reminder)));
+        for (reminder, window) in unsent {
+            if !reminders.iter().any(|(other, _)| other.is_same_time(&reminder)) {
+                reminders.push((reminder, window));
+            }
+        }
+        reminders
+    }
+
+    /// When the next reminder is due, if there is one still to come.
+    pub fn next_due(&self, config: &ReminderConfig, windows: &[EventWindow], now: &NaiveDateTime) -> Option<NaiveDateTime> {
+        self.all_reminders(config, windows).iter()
+            .map(|(reminder, _)| reminder.get_due())
+            .filter(|due| due > now)
+            .min()
+    }
+
+    /// Gives any reminders that are due to `send`, along with their window, returning whether the state changed.
+    pub fn send_due<F: FnMut(&Reminder, &EventWindow)>(&mut self, config: &ReminderConfig, windows: &[EventWindow], now: &NaiveDateTime, mut send: F) -> bool {
+        let forget_before = *now - Duration::days(FORGET_AFTER_DAYS);
+        let before = (self.sent.len(), self.cancelled.len());
+        self.sent.retain(|reminder| reminder.end > forget_before);
+        self.cancelled.retain(|(_, date)| date > &forget_before.date());
+        let mut changed = before != (self.sent.len(), self.cancelled.len());
+
+        for (reminder, window) in self.all_reminders(config, windows) {
+            let due = reminder.get_due();
+            if &due > now || *now - due > Duration::minutes(MAX_LATENESS_MINUTES) {
+                continue;
+            }
+            send(&reminder, window);
+            self.sent.insert(reminder);
+            changed = true;
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, NaiveDate};
+    use crate::reminder::{EventWindow, ReminderConfig, ReminderState};
+    use crate::resource::anticipation::{DfsIndustryNotification, IndustryNotificationType};
+
+    #[test]
+    fn test_next_due_and_cancel() {
+        let date = NaiveDate::from_ymd_opt(2023, 12, 15).unwrap();
+        let start = date.and_hms_opt(17, 0, 0).unwrap();
+        let windows = vec![EventWindow::new("live", start, start + Duration::minutes(60))];
+        let config = ReminderConfig::default();
+        let mut state = ReminderState::default();
+
+        let morning = date.and_hms_opt(9, 0, 0).unwrap();
+        assert_eq!(state.next_due(&config, &windows, &morning), Some(start - Duration::minutes(60)));
+
+        let cancelled = DfsIndustryNotification::new(IndustryNotificationType::RequirementCancelled,
+                                                     morning, "Cancelled".to_owned(), Some(date));
+        assert!(state.observe("live", &cancelled));
+        assert_eq!(state.next_due(&config, &windows, &morning), None);
+    }
+
+    #[test]
+    fn test_shared_window() {
+        let date = NaiveDate::from_ymd_opt(2023, 12, 15).unwrap();
+        let start = date.and_hms_opt(17, 0, 0).unwrap();
+        let windows = vec![EventWindow::new("live", start, start + Duration::minutes(60)),
+                           EventWindow::new("live 2023", start, start + Duration::minutes(60))];
+        let config = ReminderConfig::default();
+        let mut state = ReminderState::default();
+
+        let mut sent = 0;
+        let now = start - Duration::minutes(60);
+        assert!(state.send_due(&config, &windows, &now, |_, _| sent += 1));
+        assert_eq!(sent, 1);
+        // Not sent for the other feed later on either.
+        assert!(!state.send_due(&config, &windows[1..], &now, |_, _| sent += 1));
+        assert_eq!(sent, 1);
+        assert_eq!(state.next_due(&config, &windows, &now), Some(start - Duration::minutes(10)));
+    }
+}
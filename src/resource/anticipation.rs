@@ -1,13 +1,14 @@
-use std::error::Error;
+use std::convert::Infallible;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
-use chrono::{Local, NaiveDateTime};
+use chrono::{Local, NaiveDate, NaiveDateTime};
 use rnotifylib::message::{Level, Message};
 use rnotifylib::message::builder::MessageBuilder;
 use rnotifylib::message::component::Component;
 use rnotifylib::message::detail_builder::FormattedStringAppendable;
 use serde::{Deserialize, Serialize};
 use crate::resource::anticipation::IndustryNotificationType::*;
+use crate::resource::status::StatusDetails;
 
 #[derive(Debug, Default, Clone)]
 pub struct IndustryNotificationResource {
@@ -30,7 +31,7 @@ impl IndustryNotificationResource {
     }
 
     pub fn set(&mut self, data: DfsIndustryNotification) {
-        self.last_checked = data.when.clone();
+        self.last_checked = data.when;
         self.data = Some(data);
     }
 }
@@ -40,11 +41,13 @@ pub enum IndustryNotificationType {
     RequirementPublished,
     RequirementAnticipated,
     RequirementCancelled,
-    Test
+    Test,
+    /// A type that ESO have started using since this was written.
+    Unknown(String),
 }
 
 impl FromStr for IndustryNotificationType {
-    type Err = UnknownIndustryNotificationType;
+    type Err = Infallible;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.trim() {
@@ -54,39 +57,42 @@ impl FromStr for IndustryNotificationType {
             "Live DFS Requirement not issued" => Ok(RequirementCancelled),
             "Requirement Cancelled" => Ok(RequirementCancelled),
             "Test" => Ok(Test),
-            _ => Err(UnknownIndustryNotificationType { got: s.to_owned() })
+            other => Ok(Unknown(other.to_owned())),
         }
     }
 }
 
-#[derive(Debug)]
-pub struct UnknownIndustryNotificationType {
-    got: String,
-}
-
-impl Display for UnknownIndustryNotificationType {
+impl Display for IndustryNotificationType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Unknown notification type '{}'", self.got)
+        match self {
+            RequirementPublished => write!(f, "Requirement published"),
+            RequirementAnticipated => write!(f, "Requirement anticipated"),
+            RequirementCancelled => write!(f, "Requirement cancelled"),
+            Test => write!(f, "Test"),
+            Unknown(got) => write!(f, "Unrecognised type '{}'", got),
+        }
     }
 }
 
-impl Error for UnknownIndustryNotificationType {}
-
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DfsIndustryNotification {
     anticipation_type: IndustryNotificationType,
     when: NaiveDateTime,
     description: String,
+    #[serde(default)]
+    requirement_for: Option<NaiveDate>,
 }
 
 impl DfsIndustryNotification {
     pub fn new(anticipation_type: IndustryNotificationType,
                when: NaiveDateTime,
-               description: String) -> Self {
+               description: String,
+               requirement_for: Option<NaiveDate>) -> Self {
         Self {
             anticipation_type,
             when,
             description,
+            requirement_for,
         }
     }
 
@@ -98,31 +104,131 @@ impl DfsIndustryNotification {
         &self.when
     }
 
-    pub fn create_message(&self, live_or_test: &str) -> Message {
+    pub fn get_description(&self) -> &str {
+        &self.description
+    }
+
+    /// The date the requirement is for, only given by the newer (2023 onwards) files.
+    pub fn get_requirement_for(&self) -> &Option<NaiveDate> {
+        &self.requirement_for
+    }
+
+    /// The details ESO only gives in the free-text description, e.g. energy or system tagged.
+    pub fn get_status_details(&self) -> StatusDetails {
+        StatusDetails::parse(&self.description, &self.when)
+    }
+
+    /// The date the event would be on.
+    /// Older files don't say, so this falls back to the first date mentioned in the description.
+    pub fn get_event_date(&self) -> Option<NaiveDate> {
+        self.requirement_for
+            .or_else(|| self.get_status_details().get_dates().first().copied())
+    }
+
+    /// A short human-friendly summary, e.g. "DFS event *likely* tomorrow (Tue 24 Jan) — ESO will confirm by 14:30"
+    pub fn summary(&self, today: &NaiveDate) -> String {
+        let day = self.get_event_date()
+            .map(|date| format!(" {}", relative_day(&date, today)))
+            .unwrap_or_default();
+        match &self.anticipation_type {
+            RequirementAnticipated => match self.get_status_details().get_follow_up() {
+                Some(time) => format!("DFS event *likely*{} — ESO will confirm by {}", day, time.format("%H:%M")),
+                None => format!("DFS event *likely*{}", day),
+            },
+            RequirementPublished => format!("DFS event confirmed{}", day),
+            RequirementCancelled => format!("DFS event{} is not going ahead", day),
+            Test => format!("Test DFS event{}", day),
+            Unknown(got) => format!("Unrecognised DFS notification type '{}'{} - please check", got, day),
+        }
+    }
+
+    /// `notes` are extra lines about the event, e.g. "Anticipated notice for tomorrow was withdrawn" or the expected reward.
+    pub fn create_message(&self, live_or_test: &str, notes: &[String], level: Level, component: &str) -> Message {
         let mut message_builder = MessageBuilder::new();
+        let today = Local::now().date_naive();
+        let summary = self.summary(&today);
+        let details = self.get_status_details();
 
         message_builder
-            .level(Level::Info)
-            .title(format!("Dfs Industry Notification - {}", live_or_test))
+            .level(level)
+            .title(format!("{} - {}", self.anticipation_type, live_or_test))
             .body(|body| {
-                body.raw(format!("{:?} - {}", self, live_or_test));
+                body.raw(format!("{} - {}", summary, live_or_test));
 
                 body.text_block(|block| {
-                    block.append_plain(format!("{:?} notification pushed at {}", self.anticipation_type, self.when));
+                    block.append_plain(format!("{}\n", summary));
+                    for note in notes {
+                        block.append_plain(format!("{}\n", note));
+                    }
+                    block.append_plain(format!("{} notice issued {} at {}", self.anticipation_type,
+                                               relative_day(&self.when.date(), &today), self.when.format("%H:%M")));
                 });
 
                 body.section("Description", |builder| {
                     builder.append_plain(&self.description);
                 });
 
+                if details.get_tagging().is_some() || details.get_follow_up().is_some() {
+                    body.section("Details", |builder| {
+                        if let Some(tagging) = details.get_tagging() {
+                            builder.append_plain(format!("{}\n", tagging));
+                        }
+                        if let Some(follow_up) = details.get_follow_up() {
+                            builder.append_plain(format!("ESO will follow up by {}\n", follow_up.format("%H:%M")));
+                        }
+                    });
+                }
+
                 body.section("Links", |builder| {
                     builder.append_plain("Requirement table (incl. actual times) - https://www.nationalgrideso.com/data-portal/demand-flexibility-service/dfs_service_requirements\n");
                     builder.append_plain("Notification table - https://www.nationalgrideso.com/data-portal/demand-flexibility-service/dfs_industry_notification\n");
                 });
             })
-            .component(Component::from("dfs/industry_notification"))
+            .component(Component::from(component))
             .author("dfs_alert");
 
         message_builder.build()
     }
-}
\ No newline at end of file
+}
+/// Describes a date relative to today, e.g. "tomorrow (Tue 24 Jan)".
+pub fn relative_day(date: &NaiveDate, today: &NaiveDate) -> String {
+    let formatted = date.format("%a %d %b");
+    match (*date - *today).num_days() {
+        0 => format!("today ({})", formatted),
+        1 => format!("tomorrow ({})", formatted),
+        -1 => format!("yesterday ({})", formatted),
+        _ => format!("on {}", formatted),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use crate::resource::anticipation::{DfsIndustryNotification, IndustryNotificationType};
+
+    #[test]
+    fn test_summary() {
+        let today = NaiveDate::from_ymd_opt(2023, 1, 23).unwrap();
+        let notification = DfsIndustryNotification::new(
+            IndustryNotificationType::RequirementAnticipated,
+            today.and_hms_opt(10, 0, 0).unwrap(),
+            "The ESO may publish a DFS Service Requirement for 24/01/2023 at 14:30.".to_owned(),
+            NaiveDate::from_ymd_opt(2023, 1, 24),
+        );
+        assert_eq!(notification.summary(&today), "DFS event *likely* tomorrow (Tue 24 Jan) — ESO will confirm by 14:30");
+
+        let notification = DfsIndustryNotification::new(
+            IndustryNotificationType::RequirementPublished,
+            today.and_hms_opt(14, 30, 0).unwrap(),
+            "DFS Service Requirement has been published for tomorrow.".to_owned(),
+            None,
+        );
+        assert_eq!(notification.summary(&today), "DFS event confirmed tomorrow (Tue 24 Jan)");
+    }
+
+    #[test]
+    fn test_unknown_type() {
+        let parsed: IndustryNotificationType = " Requirement Postponed ".parse().unwrap();
+        assert_eq!(parsed, IndustryNotificationType::Unknown("Requirement Postponed".to_owned()));
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use chrono::NaiveDateTime;
+use rnotifylib::message::{Level, Message};
+use rnotifylib::message::builder::MessageBuilder;
+use rnotifylib::message::component::Component;
+use rnotifylib::message::detail_builder::FormattedStringAppendable;
+use crate::resource::Resource;
+
+/// When each resource of a datapackage was last modified, keyed by resource id.
+pub type ResourceTimestamps = BTreeMap<String, NaiveDateTime>;
+
+#[derive(Debug, Clone, PartialEq)]
+struct ModifiedResource {
+    name: String,
+    path: String,
+    /// `None` if the resource is new.
+    old: Option<NaiveDateTime>,
+    new: NaiveDateTime,
+}
+
+/// Every resource of a datapackage that was modified or removed since it was last checked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceDigest {
+    modified: Vec<ModifiedResource>,
+    /// The keys of resources that are no longer in the package, and when they were last modified.
+    removed: Vec<(String, NaiveDateTime)>,
+}
+
+impl ResourceDigest {
+    /// Compares the resources against when they were last modified, returning `None` if none were modified or removed.
+    pub fn compare(old: &ResourceTimestamps, resources: &[Resource]) -> Option<Self> {
+        let modified: Vec<ModifiedResource> = resources.iter()
+            .filter(|resource| old.get(resource.get_key()) != Some(resource.get_last_modified()))
+            .map(|resource| ModifiedResource {
+                name: resource.get_name().to_owned(),
+                path: resource.get_path().to_owned(),
+                old: old.get(resource.get_key()).copied(),
+                new: *resource.get_last_modified(),
+            })
+            .collect();
+        let removed: Vec<(String, NaiveDateTime)> = old.iter()
+            .filter(|(key, _)| !resources.iter().any(|resource| resource.get_key() == key.as_str()))
+            .map(|(key, last_modified)| (key.clone(), *last_modified))
+            .collect();
+        if modified.is_empty() && removed.is_empty() {
+            return None;
+        }
+        Some(Self {
+            modified,
+            removed,
+        })
+    }
+
+    pub fn create_message(&self, feed: &str) -> Message {
+        let modified = self.modified.iter().map(|resource| {
+            let new = resource.new.format("%d/%m/%Y %H:%M");
+            match resource.old {
+                Some(old) => format!("{}: {} -> {}", resource.name, old.format("%d/%m/%Y %H:%M"), new),
+                None => format!("{}: new, {}", resource.name, new),
+            }
+        });
+        let removed = self.removed.iter()
+            .map(|(key, last_modified)| format!("{}: removed, last modified {}", key, last_modified.format("%d/%m/%Y %H:%M")));
+        let lines: Vec<String> = modified.chain(removed).collect();
+        let mut message_builder = MessageBuilder::new();
+        message_builder
+            .level(Level::Info)
+            .title(format!("{} DFS resources updated - {}", self.modified.len() + self.removed.len(), feed))
+            .body(|body| {
+                body.raw(format!("{} - {}", lines.join("\n"), feed));
+                body.text_block(|block| {
+                    for line in &lines {
+                        block.append_plain(format!("{}\n", line));
+                    }
+                });
+                body.section("Links", |builder| {
+                    for resource in &self.modified {
+                        builder.append_plain(format!("{}\n", resource.path));
+                    }
+                });
+            })
+            .component(Component::from("dfs_alert/resources_updated"))
+            .author("dfs_alert");
+        message_builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, NaiveDate};
+    use crate::resource::digest::{ResourceDigest, ResourceTimestamps};
+    use crate::resource::Resource;
+
+    #[test]
+    fn test_compare() {
+        let before = NaiveDate::from_ymd_opt(2023, 12, 14).unwrap().and_hms_opt(10, 0, 0).unwrap();
+        let resource = Resource::new("a", "dfs_utilisation_report", before, "https://example.com/a.csv");
+        let mut old = ResourceTimestamps::new();
+        old.insert("a".to_owned(), before);
+        assert_eq!(ResourceDigest::compare(&old, &[resource]), None);
+
+        let updated = Resource::new("a", "dfs_utilisation_report", before + Duration::hours(4), "https://example.com/a.csv");
+        let added = Resource::new("b", "dfs_service_requirement", before, "https://example.com/b.csv");
+        let digest = ResourceDigest::compare(&old, &[updated, added]).expect("Should have changed");
+        assert_eq!(digest.modified.len(), 2);
+        assert_eq!(digest.modified[0].old, Some(before));
+        assert_eq!(digest.modified[1].old, None);
+        assert!(digest.removed.is_empty());
+        assert_eq!(digest.create_message("live").get_message_detail().raw(),
+                   "dfs_utilisation_report: 14/12/2023 10:00 -> 14/12/2023 14:00\ndfs_service_requirement: new, 14/12/2023 10:00 - live");
+
+        let digest = ResourceDigest::compare(&old, &[]).expect("Should have changed");
+        assert!(digest.modified.is_empty());
+        assert_eq!(digest.removed, vec![("a".to_owned(), before)]);
+    }
+}
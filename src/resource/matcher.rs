@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use regex::Regex;
+use rnotifylib::message::{Level, Message};
+use rnotifylib::message::builder::MessageBuilder;
+use rnotifylib::message::component::Component;
+use rnotifylib::message::detail_builder::FormattedStringAppendable;
+use serde::{Deserialize, Deserializer};
+use serde::de::Error as SerdeError;
+use crate::resource::Resource;
+
+const INDUSTRY_NOTIFICATION_REGEX: &str = "^(service_update_industry_notifications_|dfs_industry_notification)";
+const SERVICE_REQUIREMENT_REGEX: &str = "^dfs_service_requirement";
+const UTILISATION_REPORT_REGEX: &str = "^dfs_utilisation_report";
+
+fn optional_regex<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+    where D: Deserializer<'de> {
+    let s = String::deserialize(deserializer)?;
+    Regex::new(&s)
+        .map(Some)
+        .map_err(|err| D::Error::custom(format!("Invalid regex '{}': {}", s, err)))
+}
+
+/// Which resources of a datapackage are the file we're after.
+/// A resource matches if its id is listed, or its name matches the regex.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ResourceMatcher {
+    #[serde(default, deserialize_with = "optional_regex")]
+    name_regex: Option<Regex>,
+    #[serde(default)]
+    ids: Vec<String>,
+}
+
+impl ResourceMatcher {
+    fn from_regex(regex: &str) -> Self {
+        Self {
+            name_regex: Some(Regex::new(regex).expect("Built-in regex should be valid")),
+            ids: vec![],
+        }
+    }
+
+    pub fn matches(&self, resource: &Resource) -> bool {
+        self.ids.iter().any(|id| id == resource.get_id())
+            || self.name_regex.as_ref().filter(|regex| regex.is_match(resource.get_name())).is_some()
+    }
+
+    /// Describes what this matches, for error messages.
+    pub fn describe(&self) -> String {
+        let mut parts: Vec<String> = self.ids.iter().map(|id| format!("id {}", id)).collect();
+        if let Some(regex) = &self.name_regex {
+            parts.push(format!("name /{}/", regex));
+        }
+        parts.join(" or ")
+    }
+}
+
+fn default_industry_notification() -> ResourceMatcher {
+    ResourceMatcher::from_regex(INDUSTRY_NOTIFICATION_REGEX)
+}
+
+fn default_service_requirement() -> ResourceMatcher {
+    ResourceMatcher::from_regex(SERVICE_REQUIREMENT_REGEX)
+}
+
+fn default_utilisation_report() -> ResourceMatcher {
+    ResourceMatcher::from_regex(UTILISATION_REPORT_REGEX)
+}
+
+/// Configured with `[resources.industry_notification]`, `[resources.service_requirement]`
+/// and `[resources.utilisation_report]` tables.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ResourceMatchers {
+    #[serde(default = "default_industry_notification")]
+    industry_notification: ResourceMatcher,
+    #[serde(default = "default_service_requirement")]
+    service_requirement: ResourceMatcher,
+    #[serde(default = "default_utilisation_report")]
+    utilisation_report: ResourceMatcher,
+}
+
+impl Default for ResourceMatchers {
+    fn default() -> Self {
+        Self {
+            industry_notification: default_industry_notification(),
+            service_requirement: default_service_requirement(),
+            utilisation_report: default_utilisation_report(),
+        }
+    }
+}
+
+impl ResourceMatchers {
+    pub fn get_industry_notification(&self) -> &ResourceMatcher {
+        &self.industry_notification
+    }
+
+    pub fn get_service_requirement(&self) -> &ResourceMatcher {
+        &self.service_requirement
+    }
+
+    pub fn get_utilisation_report(&self) -> &ResourceMatcher {
+        &self.utilisation_report
+    }
+}
+
+/// Matching resources that have appeared or disappeared since last time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceChange {
+    kind: &'static str,
+    appeared: Vec<String>,
+    disappeared: Vec<String>,
+}
+
+impl ResourceChange {
+    /// Compares the matching resources by id, returning `None` if nothing changed.
+    pub fn compare(kind: &'static str, old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> Option<Self> {
+        let appeared: Vec<String> = new.iter()
+            .filter(|(id, _)| !old.contains_key(*id))
+            .map(|(_, name)| name.clone())
+            .collect();
+        let disappeared: Vec<String> = old.iter()
+            .filter(|(id, _)| !new.contains_key(*id))
+            .map(|(_, name)| name.clone())
+            .collect();
+        if appeared.is_empty() && disappeared.is_empty() {
+            return None;
+        }
+        Some(Self {
+            kind,
+            appeared,
+            disappeared,
+        })
+    }
+
+    pub fn create_message(&self, feed: &str) -> Message {
+        let appeared = self.appeared.iter().map(|name| format!("New {} resource: {}", self.kind, name));
+        let disappeared = self.disappeared.iter().map(|name| format!("{} resource removed: {}", self.kind, name));
+        let lines: Vec<String> = appeared.chain(disappeared).collect();
+        let mut message_builder = MessageBuilder::new();
+        message_builder
+            .level(Level::Info)
+            .title(format!("DFS {} resources changed - {}", self.kind, feed))
+            .body(|body| {
+                body.raw(format!("{} - {}", lines.join("\n"), feed));
+                body.text_block(|block| {
+                    for line in &lines {
+                        block.append_plain(format!("{}\n", line));
+                    }
+                });
+            })
+            .component(Component::from("dfs_alert/resources_changed"))
+            .author("dfs_alert");
+        message_builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use crate::resource::matcher::ResourceChange;
+
+    #[test]
+    fn test_compare() {
+        let mut old = BTreeMap::new();
+        old.insert("a".to_owned(), "dfs_industry_notification_2023".to_owned());
+        assert_eq!(ResourceChange::compare("industry notification", &old, &old), None);
+
+        let mut new = BTreeMap::new();
+        new.insert("b".to_owned(), "dfs_industry_notification_2024".to_owned());
+        let change = ResourceChange::compare("industry notification", &old, &new).expect("Should have changed");
+        assert_eq!(change.appeared, vec!["dfs_industry_notification_2024"]);
+        assert_eq!(change.disappeared, vec!["dfs_industry_notification_2023"]);
+    }
+}
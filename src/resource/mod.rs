@@ -1,31 +1,67 @@
-use chrono::NaiveDateTime;
+use std::collections::{BTreeMap, HashMap};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
 use error::ResourceNotFoundError;
+use crate::lifecycle::{EventLifecycle, EventStage, NoEvent};
 use crate::resource::anticipation::{IndustryNotificationResource};
+use crate::resource::digest::{ResourceDigest, ResourceTimestamps};
+use crate::resource::matcher::{ResourceChange, ResourceMatcher};
+use crate::resource::requirement::ServiceRequirementResource;
+use crate::resource::schema::SchemaDrift;
+use crate::resource::utilisation::{EventOutcome, UtilisationReportResource};
+use crate::saved_data::SavedData;
 use serde::Deserialize;
 
 pub mod error;
 pub mod anticipation;
+// Not used until supplier details are read.
+#[allow(dead_code)]
 pub mod details;
+pub mod digest;
+pub mod matcher;
+pub mod requirement;
+pub mod schema;
+pub mod status;
+pub mod utilisation;
 
 #[derive(Deserialize, Debug)]
 pub struct AvailableResources {
     resources: Vec<Resource>,
 }
 
-const PARTIAL_INDUSTRY_NOTIFICATION_MATCH: &str = "service_update_industry_notifications_";
-const NEW_INDUSTRY_NOTIFICATION_MATCH: &str = "dfs_industry_notification";
-
 impl AvailableResources {
-    pub fn get_dfs_industry_notification_resource(&self) -> Result<Resource, ResourceNotFoundError> {
-        for resource in &self.resources {
-            if resource.get_name().starts_with(PARTIAL_INDUSTRY_NOTIFICATION_MATCH)
-                || resource.get_name().starts_with(NEW_INDUSTRY_NOTIFICATION_MATCH) {
-                return Ok(resource.clone())
-            }
-        }
-        Err(ResourceNotFoundError::new(format!("{}*|{}*", PARTIAL_INDUSTRY_NOTIFICATION_MATCH, NEW_INDUSTRY_NOTIFICATION_MATCH)))
+    pub fn get_resources(&self) -> &Vec<Resource> {
+        &self.resources
+    }
+
+    /// All resources that match, most recently modified first.
+    pub fn find_matching(&self, matcher: &ResourceMatcher) -> Vec<&Resource> {
+        let mut matching: Vec<&Resource> = self.resources.iter()
+            .filter(|resource| matcher.matches(resource))
+            .collect();
+        matching.sort_by(|a, b| b.get_last_modified().cmp(a.get_last_modified()));
+        matching
+    }
+
+    fn get_latest_matching(&self, matcher: &ResourceMatcher) -> Result<Resource, ResourceNotFoundError> {
+        self.find_matching(matcher)
+            .first()
+            .map(|resource| (*resource).clone())
+            .ok_or_else(|| ResourceNotFoundError::new(matcher.describe()))
     }
 
+    pub fn get_dfs_industry_notification_resource(&self, matcher: &ResourceMatcher) -> Result<Resource, ResourceNotFoundError> {
+        self.get_latest_matching(matcher)
+    }
+
+    pub fn get_dfs_service_requirement_resource(&self, matcher: &ResourceMatcher) -> Result<Resource, ResourceNotFoundError> {
+        self.get_latest_matching(matcher)
+    }
+
+    pub fn get_dfs_utilisation_report_resource(&self, matcher: &ResourceMatcher) -> Result<Resource, ResourceNotFoundError> {
+        self.get_latest_matching(matcher)
+    }
+
+    #[allow(dead_code)]
     pub fn get_dfs_supplier_details_source(&self) -> Option<Resource> {
         todo!()
     }
@@ -33,12 +69,33 @@ impl AvailableResources {
 
 #[derive(Clone, Deserialize, Debug)]
 pub struct Resource {
+    #[serde(default)]
+    id: String,
     name: String,
     last_modified: NaiveDateTime,
     path: String,
 }
 
 impl Resource {
+    #[cfg(test)]
+    pub fn new(id: &str, name: &str, last_modified: NaiveDateTime, path: &str) -> Self {
+        Self {
+            id: id.to_owned(),
+            name: name.to_owned(),
+            last_modified,
+            path: path.to_owned(),
+        }
+    }
+
+    /// The id, or the name for packages that don't give ids.
+    pub fn get_key(&self) -> &str {
+        if self.id.is_empty() { &self.name } else { &self.id }
+    }
+
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
     pub fn get_name(&self) -> &str {
         &self.name
     }
@@ -54,6 +111,15 @@ impl Resource {
 
 pub struct PreviousResources {
     anticipated: IndustryNotificationResource,
+    requirements: ServiceRequirementResource,
+    reported_schema_drift: Vec<SchemaDrift>,
+    /// The key and name of every matching resource, keyed by what they matched for, see [`Resource::get_key`].
+    known_resources: HashMap<String, BTreeMap<String, String>>,
+    resource_timestamps: ResourceTimestamps,
+    lifecycle: EventLifecycle,
+    utilisation: UtilisationReportResource,
+    /// How past events went, for each supplier.
+    history: Vec<EventOutcome>,
     //details: CheckedDfsSupplierResource,
 }
 
@@ -61,6 +127,39 @@ impl PreviousResources {
     pub fn create(anticipated: IndustryNotificationResource) -> Self  {
         Self {
             anticipated,
+            requirements: ServiceRequirementResource::default(),
+            reported_schema_drift: vec![],
+            known_resources: HashMap::new(),
+            resource_timestamps: ResourceTimestamps::new(),
+            lifecycle: EventLifecycle::default(),
+            utilisation: UtilisationReportResource::default(),
+            history: vec![],
+        }
+    }
+
+    pub fn restore(&mut self, saved: &SavedData) {
+        if let Some(old) = saved.get_industry_notification() {
+            self.anticipated.set(old.clone());
+        }
+        self.requirements.set(saved.get_service_requirements().clone());
+        self.known_resources = saved.get_known_resources().clone();
+        self.resource_timestamps = saved.get_resource_timestamps().clone();
+        self.lifecycle = saved.get_lifecycle().clone();
+        self.utilisation.set(saved.get_utilisation_reported());
+        self.history = saved.get_history().clone();
+    }
+
+    pub fn to_saved_data(&self) -> SavedData {
+        SavedData {
+            industry_notification: self.anticipated.get_last_data().clone(),
+            service_requirements: self.requirements.get_last_data().clone(),
+            known_resources: self.known_resources.clone(),
+            resource_timestamps: self.resource_timestamps.clone(),
+            lifecycle: self.lifecycle.clone(),
+            utilisation_reported: self.utilisation.get_reported().iter().flat_map(|reported| reported.keys().copied()).collect(),
+            utilisation_rows: self.utilisation.get_reported().clone().unwrap_or_default(),
+            utilisation_read: self.utilisation.get_reported().is_some(),
+            history: self.history.clone(),
         }
     }
 
@@ -68,7 +167,112 @@ impl PreviousResources {
         &mut self.anticipated
     }
 
+    pub fn get_requirements(&mut self) -> &mut ServiceRequirementResource {
+        &mut self.requirements
+    }
+
+    pub fn get_utilisation(&mut self) -> &mut UtilisationReportResource {
+        &mut self.utilisation
+    }
+
+    /// Adds the outcome to the history, replacing any for the same event and supplier.
+    pub fn record_outcome(&mut self, outcome: EventOutcome) {
+        self.history.retain(|old| old.get_date() != outcome.get_date() || old.get_supplier() != outcome.get_supplier());
+        self.history.push(outcome);
+    }
+
+    pub fn get_history(&self) -> &Vec<EventOutcome> {
+        &self.history
+    }
+
+    /// Whether the supplier has confirmed it is taking part in the event for the date, or how it did once the results are in.
+    pub fn get_supplier_status(&self, date: &NaiveDate, supplier: &Option<String>) -> Option<&'static str> {
+        if let Some(outcome) = self.history.iter().find(|outcome| outcome.get_date() == date && outcome.get_supplier() == supplier) {
+            return Some(outcome.get_status());
+        }
+        match self.lifecycle.get_stage(date) {
+            Some(EventStage::SupplierConfirmed) => Some("confirmed"),
+            _ => None,
+        }
+    }
+
+    pub fn get_lifecycle(&mut self) -> &mut EventLifecycle {
+        &mut self.lifecycle
+    }
+
+    /// Moves events on as their windows pass, returning whether anything changed.
+    pub fn advance_lifecycle(&mut self, now: &NaiveDateTime) -> bool {
+        let windows = self.requirements.get_windows();
+        self.lifecycle.advance(&windows, now)
+    }
+
+    /// Checks whether ESO have failed to follow up on the latest anticipated notice, see [`EventLifecycle::expire_anticipated`].
+    pub fn check_no_event(&mut self, default_follow_up: NaiveTime, grace: Duration, now: &NaiveDateTime) -> Option<NoEvent> {
+        let notification = self.anticipated.get_last_data().as_ref()?;
+        self.lifecycle.expire_anticipated(notification, default_follow_up, grace, now)
+    }
+
+    /// Filters out schema drift that has already been reported, remembering what is current.
+    pub fn report_schema_drift(&mut self, current: Vec<SchemaDrift>) -> Vec<SchemaDrift> {
+        let new = current.iter()
+            .filter(|drift| !self.reported_schema_drift.contains(drift))
+            .cloned()
+            .collect();
+        self.reported_schema_drift = current;
+        new
+    }
+
+    /// Remembers which resources currently match, returning whether that changed and what to report.
+    /// Nothing is reported the first time, as there's nothing to compare to.
+    pub fn update_known_resources(&mut self, kind: &'static str, matching: &[&Resource]) -> (bool, Option<ResourceChange>) {
+        let current: BTreeMap<String, String> = matching.iter()
+            .map(|resource| (resource.get_key().to_owned(), resource.get_name().to_owned()))
+            .collect();
+        match self.known_resources.insert(kind.to_owned(), current.clone()) {
+            None => (true, None),
+            Some(old) => {
+                let change = ResourceChange::compare(kind, &old, &current);
+                (change.is_some(), change)
+            }
+        }
+    }
+
+    /// Remembers when every resource was last modified, returning whether that changed and the digest to send.
+    /// Nothing is reported the first time, as there's nothing to compare to.
+    pub fn update_resource_timestamps(&mut self, resources: &[Resource]) -> (bool, Option<ResourceDigest>) {
+        let first_time = self.resource_timestamps.is_empty();
+        let digest = ResourceDigest::compare(&self.resource_timestamps, resources);
+        let current: ResourceTimestamps = resources.iter()
+            .map(|resource| (resource.get_key().to_owned(), *resource.get_last_modified()))
+            .collect();
+        let updated = current != self.resource_timestamps;
+        self.resource_timestamps = current;
+        (updated, digest.filter(|_| !first_time))
+    }
+
     /*pub fn get_supplier_details(&self) -> &CheckedDfsSupplierResource {
         &self.details
     }*/
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use crate::resource::{PreviousResources, Resource};
+    use crate::resource::anticipation::IndustryNotificationResource;
+
+    #[test]
+    fn test_known_resources_without_ids() {
+        let when = NaiveDate::from_ymd_opt(2023, 12, 14).unwrap().and_hms_opt(10, 0, 0).unwrap();
+        let first = Resource::new("", "dfs_utilisation_report", when, "https://example.com/a.csv");
+        let second = Resource::new("", "dfs_utilisation_report_2023", when, "https://example.com/b.csv");
+        let mut resources = PreviousResources::create(IndustryNotificationResource::default());
+        assert_eq!(resources.update_known_resources("utilisation", &[&first]), (true, None));
+
+        // Told apart by their names, rather than both being "".
+        let (updated, change) = resources.update_known_resources("utilisation", &[&first, &second]);
+        assert!(updated);
+        assert!(change.is_some());
+        assert_eq!(resources.known_resources["utilisation"].len(), 2);
+    }
+}
@@ -0,0 +1,158 @@
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use crate::{naive_date_from_str, naive_time_from_str};
+use crate::region::Region;
+
+/// The service requirements file, which holds the actual times of each event.
+#[derive(Debug, Default, Clone)]
+pub struct ServiceRequirementResource {
+    last_checked: NaiveDateTime,
+    data: Vec<ServiceRequirement>,
+}
+
+impl ServiceRequirementResource {
+    pub fn get_last_checked(&self) -> &NaiveDateTime {
+        &self.last_checked
+    }
+
+    pub fn get_last_data(&self) -> &Vec<ServiceRequirement> {
+        &self.data
+    }
+
+    pub fn update(&mut self, new: Vec<ServiceRequirement>) {
+        self.data = new;
+        self.last_checked = Local::now().naive_local();
+    }
+
+    /// Restores requirements from saved state, leaving them to be re-checked.
+    pub fn set(&mut self, data: Vec<ServiceRequirement>) {
+        self.data = data;
+    }
+
+    pub fn get_on_date(&self, date: &NaiveDate) -> Vec<&ServiceRequirement> {
+        self.data.iter()
+            .filter(|r| &r.date == date)
+            .collect()
+    }
+
+    /// Just the requirements that apply to the region, or all of them if there isn't one.
+    /// Every region's requirements are kept, as profiles can be in different regions.
+    pub fn in_region(&self, region: &Option<Region>) -> Self {
+        Self {
+            last_checked: self.last_checked,
+            data: self.data.iter()
+                .filter(|r| Region::applies_to_any(region, &[r.get_region().clone()]))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// The area of each requirement on the given date, `None` for those that don't give one.
+    pub fn get_areas(&self, date: &NaiveDate) -> Vec<Option<String>> {
+        self.get_on_date(date).iter()
+            .map(|r| r.get_region().clone())
+            .collect()
+    }
+
+    /// The overall window of all requirements on the given date, if there are any.
+    pub fn get_window(&self, date: &NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        let on_date = self.get_on_date(date);
+        let start = on_date.iter().map(|r| r.get_start()).min()?;
+        let end = on_date.iter().map(|r| r.get_end()).max()?;
+        Some((start, end))
+    }
+
+    /// The highest guaranteed acceptance price of the requirements on the given date, if it was given.
+    pub fn get_price(&self, date: &NaiveDate) -> Option<f64> {
+        self.get_on_date(date).iter()
+            .filter_map(|r| r.price)
+            .reduce(f64::max)
+    }
+
+    /// The window of each date that has requirements.
+    pub fn get_windows(&self) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        let mut dates: Vec<NaiveDate> = self.data.iter().map(|r| r.date).collect();
+        dates.sort();
+        dates.dedup();
+        dates.iter().filter_map(|date| self.get_window(date)).collect()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ServiceRequirement {
+    #[serde(rename = "Delivery Date")]
+    #[serde(alias = "Date")]
+    #[serde(deserialize_with = "naive_date_from_str")]
+    date: NaiveDate,
+    #[serde(rename = "From")]
+    #[serde(deserialize_with = "naive_time_from_str")]
+    from: NaiveTime,
+    #[serde(rename = "To")]
+    #[serde(deserialize_with = "naive_time_from_str")]
+    to: NaiveTime,
+    #[serde(rename = "Service Requirement MW")]
+    #[serde(default)]
+    requirement_mw: Option<f64>,
+    /// The GSP group or region the requirement is for, blank if it is national.
+    #[serde(rename = "Region")]
+    #[serde(default)]
+    region: Option<String>,
+    #[serde(rename = "Guaranteed Acceptance Price GBP per MWh")]
+    #[serde(default)]
+    price: Option<f64>,
+}
+
+impl ServiceRequirement {
+    pub fn get_start(&self) -> NaiveDateTime {
+        self.date.and_time(self.from)
+    }
+
+    pub fn get_end(&self) -> NaiveDateTime {
+        self.date.and_time(self.to)
+    }
+
+    pub fn get_region(&self) -> &Option<String> {
+        &self.region
+    }
+}
+
+/// The earliest of the windows that hasn't finished yet.
+/// Give it whole event windows, e.g. from [`ServiceRequirementResource::get_windows`], rather than each half hour.
+pub fn next_window<I>(windows: I, now: &NaiveDateTime) -> Option<(NaiveDateTime, NaiveDateTime)>
+    where I: IntoIterator<Item = (NaiveDateTime, NaiveDateTime)> {
+    windows.into_iter()
+        .filter(|(_, end)| end > now)
+        .min()
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use csv::Trim;
+    use crate::resource::requirement::{next_window, ServiceRequirement, ServiceRequirementResource};
+
+    #[test]
+    fn test_window() {
+        let s = "Delivery Date,From,To,Service Requirement MW,Dispatch Type
+15/12/2023,17:00,17:30,400,Live
+15/12/2023,17:30,18:00,, Live ";
+        let mut rdr = csv::ReaderBuilder::new()
+            .trim(Trim::All)
+            .from_reader(s.as_bytes());
+        let requirements: Vec<ServiceRequirement> = rdr.deserialize()
+            .collect::<Result<_, _>>()
+            .expect("Should be able to deserialize");
+        let mut resource = ServiceRequirementResource::default();
+        resource.update(requirements);
+
+        let date = NaiveDate::from_ymd_opt(2023, 12, 15).unwrap();
+        let (start, end) = resource.get_window(&date).expect("Should have a window");
+        assert_eq!(start, date.and_hms_opt(17, 0, 0).unwrap());
+        assert_eq!(end, date.and_hms_opt(18, 0, 0).unwrap());
+
+        // The end of the whole event, not of the first half hour.
+        let during = date.and_hms_opt(17, 10, 0).unwrap();
+        assert_eq!(next_window(resource.get_windows(), &during), Some((start, end)));
+        assert_eq!(next_window(resource.get_windows(), &end), None);
+    }
+}
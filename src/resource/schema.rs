@@ -0,0 +1,297 @@
+use std::collections::{HashMap, HashSet};
+use csv::{ReaderBuilder, StringRecord, Trim};
+use rnotifylib::message::{Level, Message};
+use rnotifylib::message::builder::MessageBuilder;
+use rnotifylib::message::component::Component;
+use rnotifylib::message::detail_builder::FormattedStringAppendable;
+use serde::de::DeserializeOwned;
+
+/// Extra header names for each column, keyed by the column name dfs_alert expects.
+/// Configured with a `[csv_aliases]` table, e.g. `"Date" = ["Issue Date"]`.
+pub type HeaderAliases = HashMap<String, Vec<String>>;
+
+/// The columns expected in one of the ESO csv files.
+pub struct CsvSchema {
+    name: &'static str,
+    required: &'static [&'static str],
+    optional: &'static [&'static str],
+    /// Columns that are known about, but not used.
+    ignored: &'static [&'static str],
+    /// (alias, column) pairs for headers ESO have used before.
+    aliases: &'static [(&'static str, &'static str)],
+    /// Whether to warn about columns that aren't known about at all.
+    warn_unknown: bool,
+}
+
+pub const INDUSTRY_NOTIFICATION_SCHEMA: CsvSchema = CsvSchema {
+    name: "industry notification",
+    required: &["Date", "Status", "Type", "Time"],
+    optional: &["Requirement For"],
+    ignored: &["Notification Type"],
+    aliases: &[
+        ("Notification Issued Date", "Date"),
+        ("Notification Issued Time", "Time"),
+        ("Requirement Type", "Type"),
+    ],
+    warn_unknown: true,
+};
+
+pub const SERVICE_REQUIREMENT_SCHEMA: CsvSchema = CsvSchema {
+    name: "service requirement",
+    required: &["Delivery Date", "From", "To"],
+    optional: &["Service Requirement MW", "Region", "Guaranteed Acceptance Price GBP per MWh"],
+    ignored: &[],
+    aliases: &[
+        ("Date", "Delivery Date"),
+        ("GAP GBP per MWh", "Guaranteed Acceptance Price GBP per MWh"),
+        ("GSP Group", "Region"),
+        ("DNO Region", "Region"),
+        ("Zone", "Region"),
+    ],
+    // This file has lots of columns that don't matter to us.
+    warn_unknown: false,
+};
+
+pub const UTILISATION_REPORT_SCHEMA: CsvSchema = CsvSchema {
+    name: "utilisation report",
+    required: &["Delivery Date", "From", "To", "Registered DFS Participant", "Status"],
+    optional: &["DFS Volume MW", "Utilisation Price GBP per MWh"],
+    ignored: &[],
+    aliases: &[
+        ("Date", "Delivery Date"),
+        ("Participant", "Registered DFS Participant"),
+        ("Provider", "Registered DFS Participant"),
+        ("Volume MW", "DFS Volume MW"),
+        ("Price GBP per MWh", "Utilisation Price GBP per MWh"),
+        ("Guaranteed Acceptance Price GBP per MWh", "Utilisation Price GBP per MWh"),
+    ],
+    warn_unknown: false,
+};
+
+/// Smart meter exports, e.g. from the supplier's website, rather than an ESO file.
+pub const METER_READING_SCHEMA: CsvSchema = CsvSchema {
+    name: "meter reading",
+    required: &["Consumption (kWh)", "Start"],
+    optional: &[],
+    ignored: &["End"],
+    aliases: &[
+        ("Consumption", "Consumption (kWh)"),
+        ("consumption_kwh", "Consumption (kWh)"),
+        ("interval_start", "Start"),
+        ("Start Time", "Start"),
+        ("interval_end", "End"),
+        ("End Time", "End"),
+    ],
+    warn_unknown: false,
+};
+
+/// The rows of a csv file, along with how its headers differ from what was expected.
+pub struct CsvRows<T> {
+    records: Vec<T>,
+    drift: Option<SchemaDrift>,
+}
+
+impl<T> CsvRows<T> {
+    pub fn into_parts(self) -> (Vec<T>, Option<SchemaDrift>) {
+        (self.records, self.drift)
+    }
+}
+
+fn normalise(header: &str) -> String {
+    header.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl CsvSchema {
+    /// The column name that a header refers to, if it is known about.
+    fn column_for(&self, header: &str, aliases: &HeaderAliases) -> Option<String> {
+        let header = normalise(header);
+        let known = self.required.iter()
+            .chain(self.optional)
+            .chain(self.ignored)
+            .find(|column| normalise(column) == header);
+        if let Some(column) = known {
+            return Some(column.to_string());
+        }
+        let built_in = self.aliases.iter()
+            .find(|(alias, _)| normalise(alias) == header)
+            .map(|(_, column)| column.to_string());
+        if built_in.is_some() {
+            return built_in;
+        }
+        aliases.iter()
+            .find(|(_, names)| names.iter().any(|name| normalise(name) == header))
+            .map(|(column, _)| column.clone())
+    }
+
+    /// Reads the csv, trimming whitespace, skipping blank or padding rows and mapping headers to the expected columns.
+    /// If any required column is missing, no rows are read and only the drift is returned.
+    pub fn read<T: DeserializeOwned>(&self, csv: &str, aliases: &HeaderAliases) -> Result<CsvRows<T>, csv::Error> {
+        let mut reader = ReaderBuilder::new()
+            .trim(Trim::All)
+            .flexible(true)
+            .from_reader(csv.as_bytes());
+
+        let headers: Vec<String> = reader.headers()?.iter().map(str::to_owned).collect();
+        // Only the first header for each column is mapped, as serde rejects rows with a field twice.
+        let mut mapped = HashSet::new();
+        let columns: Vec<Option<String>> = headers.iter()
+            .map(|header| self.column_for(header, aliases))
+            .collect();
+        let duplicates: Vec<bool> = columns.iter()
+            .map(|column| column.as_ref().is_some_and(|column| !mapped.insert(column.clone())))
+            .collect();
+        let columns: Vec<Option<String>> = columns.into_iter().zip(&duplicates)
+            .map(|(column, duplicate)| column.filter(|_| !duplicate))
+            .collect();
+
+        let unknown: Vec<String> = headers.iter().zip(&columns)
+            .filter(|(header, column)| column.is_none() && !header.is_empty())
+            .map(|(header, _)| header.clone())
+            .collect();
+        let missing: Vec<String> = self.required.iter()
+            .filter(|required| !columns.iter().any(|column| column.as_deref() == Some(**required)))
+            .map(|required| required.to_string())
+            .collect();
+
+        // A column given twice is always worth knowing about, as the wrong one may have been used.
+        let duplicated = duplicates.iter().any(|duplicate| *duplicate);
+        let drift = if !missing.is_empty() || duplicated || (self.warn_unknown && !unknown.is_empty()) {
+            Some(SchemaDrift {
+                schema: self.name,
+                headers: headers.clone(),
+                unknown,
+                missing: missing.clone(),
+            })
+        } else {
+            None
+        };
+
+        if !missing.is_empty() {
+            return Ok(CsvRows {
+                records: vec![],
+                drift,
+            });
+        }
+
+        // Duplicates are blanked, as their own header could be the column's name.
+        let mapped_headers: StringRecord = headers.iter().zip(columns).zip(duplicates)
+            .map(|((header, column), duplicate)| match column {
+                Some(column) => column,
+                None if duplicate => String::new(),
+                None => header.clone(),
+            })
+            .collect();
+
+        let mut records = vec![];
+        for record in reader.records() {
+            let record = record?;
+            if record.iter().all(str::is_empty) {
+                continue;
+            }
+            records.push(record.deserialize(Some(&mapped_headers))?);
+        }
+        Ok(CsvRows {
+            records,
+            drift,
+        })
+    }
+}
+
+/// The headers of a file no longer match what was expected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaDrift {
+    schema: &'static str,
+    headers: Vec<String>,
+    unknown: Vec<String>,
+    missing: Vec<String>,
+}
+
+impl SchemaDrift {
+    pub fn create_message(&self, feed: &str) -> Message {
+        let mut lines = vec![format!("The {} file on {} has different columns to what was expected.", self.schema, feed)];
+        if !self.missing.is_empty() {
+            lines.push(format!("Missing: {}", self.missing.join(", ")));
+        }
+        if !self.unknown.is_empty() {
+            lines.push(format!("Unknown: {}", self.unknown.join(", ")));
+        }
+        let mut message_builder = MessageBuilder::new();
+        message_builder
+            .level(Level::Warn)
+            .title(format!("DFS {} file format changed - {}", self.schema, feed))
+            .body(|body| {
+                body.raw(format!("{}\nHeaders: {}", lines.join("\n"), self.headers.join(", ")));
+                body.text_block(|block| {
+                    for line in &lines {
+                        block.append_plain(format!("{}\n", line));
+                    }
+                });
+                body.section("Headers", |builder| {
+                    builder.append_plain(self.headers.join(", "));
+                });
+            })
+            .component(Component::from("dfs_alert/schema_changed"))
+            .author("dfs_alert");
+        message_builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use crate::DfsIndustryNotificationResponse;
+    use crate::resource::schema::{INDUSTRY_NOTIFICATION_SCHEMA, UTILISATION_REPORT_SCHEMA};
+    use crate::resource::utilisation::UtilisationRow;
+
+    #[test]
+    fn test_padding_and_whitespace() {
+        let s = " Notification Issued Date , Notification Issued Time,Requirement For,Requirement Type,Status,Notification Type
+14/12/2023, 14:30 ,15/12/2023,Test,DFS Service Requirement has been published for 15/12/2023. This is System Tagged.,Requirement Published
+,,,,,
+,,,,,
+";
+        let rows = INDUSTRY_NOTIFICATION_SCHEMA.read::<DfsIndustryNotificationResponse>(s, &HashMap::new())
+            .expect("Should be able to read");
+        let (records, drift) = rows.into_parts();
+        assert_eq!(records.len(), 1);
+        assert!(drift.is_none());
+    }
+
+    #[test]
+    fn test_drift() {
+        let s = "Issued On,Status,Type,Time,Region
+2023-01-23,Something,Test,10:00,East";
+        let rows = INDUSTRY_NOTIFICATION_SCHEMA.read::<DfsIndustryNotificationResponse>(s, &HashMap::new())
+            .expect("Should be able to read");
+        let (records, drift) = rows.into_parts();
+        assert!(records.is_empty());
+        let drift = drift.expect("Should have drifted");
+        assert_eq!(drift.missing, vec!["Date"]);
+        assert_eq!(drift.unknown, vec!["Issued On", "Region"]);
+
+        let mut aliases = HashMap::new();
+        aliases.insert("Date".to_owned(), vec!["issued on".to_owned()]);
+        let rows = INDUSTRY_NOTIFICATION_SCHEMA.read::<DfsIndustryNotificationResponse>(s, &aliases)
+            .expect("Should be able to read");
+        let (records, drift) = rows.into_parts();
+        assert_eq!(records.len(), 1);
+        assert_eq!(drift.expect("Region is still unknown").unknown, vec!["Region"]);
+    }
+
+    #[test]
+    fn test_duplicate_column() {
+        let read = |s: &str| UTILISATION_REPORT_SCHEMA.read::<UtilisationRow>(s, &HashMap::new())
+            .expect("Should be able to read")
+            .into_parts();
+        let (records, drift) = read("Delivery Date,From,To,Registered DFS Participant,DFS Volume MW,Price GBP per MWh,Utilisation Price GBP per MWh,Status
+15/12/2023,17:00,17:30,OCTOPUS ENERGY LTD,100,3000,2000,Accepted");
+        let (expected, _) = read("Delivery Date,From,To,Registered DFS Participant,DFS Volume MW,Utilisation Price GBP per MWh,Status
+15/12/2023,17:00,17:30,OCTOPUS ENERGY LTD,100,3000,Accepted");
+        // The first header for the price is used.
+        assert_eq!(records, expected);
+        assert_eq!(drift.expect("Should report the second price").unknown, vec!["Utilisation Price GBP per MWh"]);
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt::{Display, Formatter};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::Serialize;
+
+/// How the DFS requirement is tagged, given only in the free-text status.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Tagging {
+    EnergyTagged,
+    SystemTagged,
+}
+
+impl Display for Tagging {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tagging::EnergyTagged => write!(f, "Energy tagged"),
+            Tagging::SystemTagged => write!(f, "System tagged"),
+        }
+    }
+}
+
+/// What could be worked out from the status description of a notification.
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct StatusDetails {
+    tagging: Option<Tagging>,
+    /// Dates referred to, in the order they were mentioned. "today" and "tomorrow" are relative to the issue date.
+    dates: Vec<NaiveDate>,
+    /// When ESO said they would follow up, e.g. "might be published today at 14:30".
+    follow_up: Option<NaiveTime>,
+}
+
+impl StatusDetails {
+    /// Picks out what it can from the description, ignoring anything it doesn't recognise.
+    pub fn parse(description: &str, issued: &NaiveDateTime) -> Self {
+        let lower = description.to_lowercase();
+
+        let tagging = if lower.contains("energy tagged") {
+            Some(Tagging::EnergyTagged)
+        }
+        else if lower.contains("system tagged") {
+            Some(Tagging::SystemTagged)
+        }
+        else {
+            None
+        };
+
+        let mut dates = vec![];
+        let mut follow_up = None;
+        for word in lower.split(|c: char| c.is_whitespace() || c == ',' || c == '(' || c == ')') {
+            let word = word.trim_end_matches('.');
+            let date = match word {
+                "today" => Some(issued.date()),
+                "tomorrow" => Some(issued.date() + Duration::days(1)),
+                _ => NaiveDate::parse_from_str(word, "%d/%m/%Y")
+                    .or_else(|_| NaiveDate::parse_from_str(word, "%Y-%m-%d"))
+                    .ok(),
+            };
+            if let Some(date) = date {
+                if !dates.contains(&date) {
+                    dates.push(date);
+                }
+            }
+            if follow_up.is_none() {
+                follow_up = NaiveTime::parse_from_str(word, "%H:%M").ok();
+            }
+        }
+
+        Self {
+            tagging,
+            dates,
+            follow_up,
+        }
+    }
+
+    pub fn get_tagging(&self) -> &Option<Tagging> {
+        &self.tagging
+    }
+
+    pub fn get_dates(&self) -> &Vec<NaiveDate> {
+        &self.dates
+    }
+
+    pub fn get_follow_up(&self) -> &Option<NaiveTime> {
+        &self.follow_up
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+    use crate::resource::status::{StatusDetails, Tagging};
+
+    struct Case {
+        issued: &'static str,
+        description: &'static str,
+        tagging: Option<Tagging>,
+        dates: &'static [&'static str],
+        follow_up: Option<&'static str>,
+    }
+
+    /// Status descriptions from past rows of the industry notification files.
+    const CORPUS: &[Case] = &[
+        Case {
+            issued: "2023-01-22 14:30",
+            description: "DFS Service Requirement has been published for tomorrow. Please view the service requirement file in this dataset for details of the required volumes and times. This will be Energy Tagged ",
+            tagging: Some(Tagging::EnergyTagged),
+            dates: &["2023-01-23"],
+            follow_up: None,
+        },
+        Case {
+            issued: "2023-01-23 10:00",
+            description: "This is an indication that a DFS Service Requirement might be published today at 14:30. This will be Energy Tagged. ",
+            tagging: Some(Tagging::EnergyTagged),
+            dates: &["2023-01-23"],
+            follow_up: Some("14:30"),
+        },
+        Case {
+            issued: "2023-12-14 14:30",
+            description: "DFS Service Requirement has been published for 15/12/2023. This is System Tagged.",
+            tagging: Some(Tagging::SystemTagged),
+            dates: &["2023-12-15"],
+            follow_up: None,
+        },
+        Case {
+            issued: "2023-12-14 10:00",
+            description: "The ESO may publish a DFS Service Requirement for 15/12/2023. This will be System Tagged. ",
+            tagging: Some(Tagging::SystemTagged),
+            dates: &["2023-12-15"],
+            follow_up: None,
+        },
+    ];
+
+    #[test]
+    fn test_corpus() {
+        for case in CORPUS {
+            let issued = NaiveDateTime::parse_from_str(case.issued, "%Y-%m-%d %H:%M").unwrap();
+            let details = StatusDetails::parse(case.description, &issued);
+            let dates: Vec<NaiveDate> = case.dates.iter()
+                .map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap())
+                .collect();
+            assert_eq!(details.get_tagging(), &case.tagging, "{}", case.description);
+            assert_eq!(details.get_dates(), &dates, "{}", case.description);
+            assert_eq!(details.get_follow_up(), &case.follow_up.map(|t| NaiveTime::parse_from_str(t, "%H:%M").unwrap()), "{}", case.description);
+        }
+    }
+
+    #[test]
+    fn test_unrecognised() {
+        let issued = NaiveDateTime::parse_from_str("2023-12-14 10:00", "%Y-%m-%d %H:%M").unwrap();
+        assert_eq!(StatusDetails::parse("Something new", &issued), StatusDetails::default());
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::{BTreeMap, BTreeSet};
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime};
+use rnotifylib::message::{Level, Message};
+use rnotifylib::message::builder::MessageBuilder;
+use rnotifylib::message::component::Component;
+use rnotifylib::message::detail_builder::FormattedStringAppendable;
+use serde::{Deserialize, Serialize};
+use crate::{naive_date_from_str, naive_time_from_str};
+use crate::resource::anticipation::relative_day;
+use crate::reward::RewardConfig;
+use crate::supplier::SupplierRegistry;
+
+/// The utilisation report, which says which bids were accepted after each event.
+#[derive(Debug, Default, Clone)]
+pub struct UtilisationReportResource {
+    last_checked: NaiveDateTime,
+    /// How many rows each event date had when its results were reported.
+    /// 0 for dates from older saved state, where the number isn't known.
+    /// `None` until the report has been read, even if it had no rows.
+    reported: Option<BTreeMap<NaiveDate, usize>>,
+}
+
+impl UtilisationReportResource {
+    pub fn get_last_checked(&self) -> &NaiveDateTime {
+        &self.last_checked
+    }
+
+    pub fn get_reported(&self) -> &Option<BTreeMap<NaiveDate, usize>> {
+        &self.reported
+    }
+
+    /// Restores the reported dates from saved state, leaving the report to be re-checked.
+    pub fn set(&mut self, reported: Option<BTreeMap<NaiveDate, usize>>) {
+        self.reported = reported;
+    }
+
+    fn count_rows(rows: &[UtilisationRow]) -> BTreeMap<NaiveDate, usize> {
+        let mut counts = BTreeMap::new();
+        for row in rows {
+            *counts.entry(row.date).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Every row for the dates that haven't been reported, or have gained rows since they were.
+    /// Nothing is returned the first time, so that every past event isn't reported at once.
+    pub fn get_unreported(&self, rows: &[UtilisationRow]) -> Vec<UtilisationRow> {
+        let reported = match &self.reported {
+            Some(reported) => reported,
+            None => return vec![],
+        };
+        let unreported: BTreeSet<NaiveDate> = Self::count_rows(rows).into_iter()
+            .filter(|(date, count)| match reported.get(date) {
+                Some(0) => false,
+                Some(reported) => reported != count,
+                None => true,
+            })
+            .map(|(date, _)| date)
+            .collect();
+        rows.iter()
+            .filter(|row| unreported.contains(&row.date))
+            .cloned()
+            .collect()
+    }
+
+    /// Records the rows as reported, once their results have been sent, returning whether that changed anything saved.
+    pub fn mark_reported(&mut self, rows: &[UtilisationRow]) -> bool {
+        self.last_checked = Local::now().naive_local();
+        let mut changed = self.reported.is_none();
+        let reported = self.reported.get_or_insert_with(BTreeMap::new);
+        for (date, count) in Self::count_rows(rows) {
+            changed |= reported.insert(date, count) != Some(count);
+        }
+        changed
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct UtilisationRow {
+    #[serde(rename = "Delivery Date")]
+    #[serde(deserialize_with = "naive_date_from_str")]
+    date: NaiveDate,
+    #[serde(rename = "From")]
+    #[serde(deserialize_with = "naive_time_from_str")]
+    from: NaiveTime,
+    #[serde(rename = "To")]
+    #[serde(deserialize_with = "naive_time_from_str")]
+    to: NaiveTime,
+    #[serde(rename = "Registered DFS Participant")]
+    participant: String,
+    #[serde(rename = "DFS Volume MW")]
+    #[serde(default)]
+    volume_mw: Option<f64>,
+    #[serde(rename = "Utilisation Price GBP per MWh")]
+    #[serde(default)]
+    price: Option<f64>,
+    #[serde(rename = "Status")]
+    status: String,
+}
+
+impl UtilisationRow {
+    pub fn get_date(&self) -> &NaiveDate {
+        &self.date
+    }
+
+    fn is_accepted(&self) -> bool {
+        self.status.trim().to_lowercase().starts_with("accept")
+    }
+
+    fn get_volume(&self) -> f64 {
+        self.volume_mw.unwrap_or_default()
+    }
+}
+
+/// How an event went for a supplier, kept in saved state as history.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EventOutcome {
+    date: NaiveDate,
+    start: NaiveTime,
+    end: NaiveTime,
+    /// The canonical supplier id, or `None` if the profile's supplier isn't known.
+    supplier: Option<String>,
+    accepted_bids: usize,
+    total_bids: usize,
+    accepted_mw: f64,
+    /// Weighted by volume.
+    average_price: Option<f64>,
+    /// Across every participant.
+    all_accepted_mw: f64,
+    participants: usize,
+}
+
+impl EventOutcome {
+    /// Summarises the rows for the date from the point of view of the supplier.
+    pub fn summarise(date: NaiveDate, rows: &[UtilisationRow], supplier: &Option<String>, suppliers: &SupplierRegistry) -> Option<Self> {
+        let on_date: Vec<&UtilisationRow> = rows.iter().filter(|row| row.date == date).collect();
+        let start = on_date.iter().map(|row| row.from).min()?;
+        let end = on_date.iter().map(|row| row.to).max()?;
+        let ours: Vec<&&UtilisationRow> = on_date.iter()
+            .filter(|row| supplier.is_some() && suppliers.identify(&row.participant) == supplier.as_deref())
+            .collect();
+        let accepted: Vec<&&&UtilisationRow> = ours.iter().filter(|row| row.is_accepted()).collect();
+        let accepted_mw: f64 = accepted.iter().map(|row| row.get_volume()).sum();
+        let average_price = Some(accepted.iter()
+            .filter_map(|row| row.price.map(|price| price * row.get_volume()))
+            .sum::<f64>() / accepted_mw)
+            .filter(|price| price.is_finite());
+        let participants: BTreeSet<&str> = on_date.iter()
+            .filter(|row| row.is_accepted())
+            .map(|row| suppliers.identify(&row.participant).unwrap_or(&row.participant))
+            .collect();
+        Some(Self {
+            date,
+            start,
+            end,
+            supplier: supplier.clone(),
+            accepted_bids: accepted.len(),
+            total_bids: ours.len(),
+            accepted_mw,
+            average_price,
+            all_accepted_mw: on_date.iter().filter(|row| row.is_accepted()).map(|row| row.get_volume()).sum(),
+            participants: participants.len(),
+        })
+    }
+
+    pub fn get_date(&self) -> &NaiveDate {
+        &self.date
+    }
+
+    pub fn get_supplier(&self) -> &Option<String> {
+        &self.supplier
+    }
+
+    /// How the supplier did, as given in webhook payloads.
+    pub fn get_status(&self) -> &'static str {
+        if self.total_bids == 0 {
+            "no_bid"
+        } else if self.accepted_bids == 0 {
+            "rejected"
+        } else {
+            "accepted"
+        }
+    }
+
+    /// Whether the supplier bid, and how many of its bids were accepted.
+    pub fn describe(&self, today: &NaiveDate) -> String {
+        let supplier = self.supplier.as_deref().unwrap_or("our supplier");
+        if self.total_bids == 0 {
+            format!("{} didn't bid for the DFS event {}", supplier, relative_day(&self.date, today))
+        } else {
+            format!("{} had {}/{} bids accepted for the DFS event {}", supplier, self.accepted_bids, self.total_bids, relative_day(&self.date, today))
+        }
+    }
+
+    pub fn create_message(&self, feed: &str, reward: &Option<RewardConfig>) -> Message {
+        let summary = self.describe(&Local::now().date_naive());
+        let window = format!("{} - {}", self.start.format("%H:%M"), self.end.format("%H:%M"));
+        let mut message_builder = MessageBuilder::new();
+        message_builder
+            .level(Level::Info)
+            .title(format!("DFS event results - {}", feed))
+            .body(|body| {
+                body.raw(format!("{} ({}) - {}", summary, window, feed));
+                body.text_block(|block| {
+                    block.append_plain(format!("{}\n", summary));
+                    block.append_plain(format!("Event window: {}\n", window));
+                });
+                body.section("Results", |builder| {
+                    builder.append_plain(format!("Accepted volume: {:.1} MW\n", self.accepted_mw));
+                    if let Some(price) = self.average_price {
+                        builder.append_plain(format!("Average price: £{:.2}/MWh\n", price));
+                        if let Some(reward) = reward {
+                            builder.append_plain(format!("{}\n", reward.describe(price)));
+                        }
+                    }
+                    builder.append_plain(format!("Overall: {:.1} MW accepted from {} participants\n", self.all_accepted_mw, self.participants));
+                });
+            })
+            .component(Component::from("dfs/results"))
+            .author("dfs_alert");
+        message_builder.build()
+    }
+}
+
+/// Participants in the rows that aren't known suppliers, so that someone can add them.
+pub fn unknown_participants(rows: &[UtilisationRow], suppliers: &SupplierRegistry) -> BTreeSet<String> {
+    rows.iter()
+        .filter(|row| suppliers.identify(&row.participant).is_none())
+        .map(|row| row.participant.clone())
+        .collect()
+}
+
+/// Warns that suppliers in the data don't match any known alias.
+pub fn create_unknown_suppliers_message(unknown: &BTreeSet<String>, feed: &str) -> Message {
+    let mut message_builder = MessageBuilder::new();
+    message_builder
+        .level(Level::Warn)
+        .title(format!("Unknown DFS suppliers - {}", feed))
+        .body(|body| {
+            let names: Vec<&str> = unknown.iter().map(String::as_str).collect();
+            body.raw(format!("Unknown suppliers in the utilisation report: {} - {}", names.join(", "), feed));
+            body.text_block(|block| {
+                block.append_plain("These suppliers in the utilisation report don't match any known supplier, add them as a [[supplier]] in the config:\n");
+                for name in unknown {
+                    block.append_plain(format!("{}\n", name));
+                }
+            });
+        })
+        .component(Component::from("dfs_alert/unknown_supplier"))
+        .author("dfs_alert");
+    message_builder.build()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use chrono::NaiveDate;
+    use crate::resource::schema::UTILISATION_REPORT_SCHEMA;
+    use crate::resource::utilisation::{EventOutcome, unknown_participants, UtilisationReportResource, UtilisationRow};
+    use crate::supplier::SupplierRegistry;
+
+    #[test]
+    fn test_summarise() {
+        let s = "Delivery Date,From,To,Registered DFS Participant,DFS Volume MW,Utilisation Price GBP per MWh,Status
+15/12/2023,17:00,17:30,OCTOPUS ENERGY LTD,100,3000,Accepted
+15/12/2023,17:30,18:00,Octopus Energy Limited,50,6000,Accepted
+15/12/2023,17:30,18:00,Octopus Energy Limited,20,9000,Rejected
+15/12/2023,17:00,18:00,Someone New Ltd,10,3000,Accepted";
+        let (rows, drift) = UTILISATION_REPORT_SCHEMA.read::<UtilisationRow>(s, &HashMap::new())
+            .expect("Should be able to read")
+            .into_parts();
+        assert!(drift.is_none());
+        let suppliers = SupplierRegistry::new(&[]);
+        let date = NaiveDate::from_ymd_opt(2023, 12, 15).unwrap();
+        let outcome = EventOutcome::summarise(date, &rows, &Some("octopus".to_owned()), &suppliers)
+            .expect("Should have an outcome");
+        assert_eq!((outcome.accepted_bids, outcome.total_bids), (2, 3));
+        assert_eq!(outcome.accepted_mw, 150.0);
+        assert_eq!(outcome.average_price, Some(4000.0));
+        assert_eq!(outcome.all_accepted_mw, 160.0);
+        assert_eq!(outcome.participants, 2);
+        assert_eq!(unknown_participants(&rows, &suppliers).into_iter().collect::<Vec<_>>(), vec!["Someone New Ltd"]);
+    }
+
+    #[test]
+    fn test_unreported() {
+        let read = |s: &str| UTILISATION_REPORT_SCHEMA.read::<UtilisationRow>(s, &HashMap::new())
+            .expect("Should be able to read")
+            .into_parts()
+            .0;
+        let header = "Delivery Date,From,To,Registered DFS Participant,DFS Volume MW,Utilisation Price GBP per MWh,Status\n";
+        let old = read(&format!("{}14/12/2023,17:00,17:30,OCTOPUS ENERGY LTD,100,3000,Accepted", header));
+        let mut resource = UtilisationReportResource::default();
+        // Past events aren't reported the first time.
+        assert!(resource.get_unreported(&old).is_empty());
+        assert!(resource.mark_reported(&old));
+
+        let rows = read(&format!("{}14/12/2023,17:00,17:30,OCTOPUS ENERGY LTD,100,3000,Accepted
+15/12/2023,17:00,17:30,OCTOPUS ENERGY LTD,100,3000,Accepted", header));
+        assert_eq!(resource.get_unreported(&rows), rows[1..].to_vec());
+        // Not until they have been marked as reported.
+        assert_eq!(resource.get_unreported(&rows), rows[1..].to_vec());
+        assert!(resource.mark_reported(&rows));
+        assert!(resource.get_unreported(&rows).is_empty());
+        assert!(!resource.mark_reported(&rows));
+
+        // A date that gains rows is summarised again, with all of them.
+        let more = read(&format!("{}14/12/2023,17:00,17:30,OCTOPUS ENERGY LTD,100,3000,Accepted
+15/12/2023,17:00,17:30,OCTOPUS ENERGY LTD,100,3000,Accepted
+15/12/2023,17:30,18:00,OCTOPUS ENERGY LTD,50,3000,Accepted", header));
+        assert_eq!(resource.get_unreported(&more), more[1..].to_vec());
+    }
+
+    #[test]
+    fn test_first_event_reported() {
+        let read = |s: &str| UTILISATION_REPORT_SCHEMA.read::<UtilisationRow>(s, &HashMap::new())
+            .expect("Should be able to read")
+            .into_parts()
+            .0;
+        let header = "Delivery Date,From,To,Registered DFS Participant,DFS Volume MW,Utilisation Price GBP per MWh,Status\n";
+        let mut resource = UtilisationReportResource::default();
+        // A new package's report starts out empty.
+        assert!(resource.get_unreported(&[]).is_empty());
+        assert!(resource.mark_reported(&[]));
+        assert!(!resource.mark_reported(&[]));
+
+        // So its first event is still reported.
+        let rows = read(&format!("{}15/12/2023,17:00,17:30,OCTOPUS ENERGY LTD,100,3000,Accepted", header));
+        assert_eq!(resource.get_unreported(&rows), rows);
+    }
+}
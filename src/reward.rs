@@ -0,0 +1,46 @@
+use serde::Deserialize;
+
+fn default_typical_reduction_kwh() -> f64 {
+    1.0
+}
+
+fn default_pass_through() -> f64 {
+    1.0
+}
+
+/// How much a household typically turns down during an event, and how much of the price its supplier passes on.
+/// Configured with a `[reward]` table in `dfs_alert.toml`, or in a profile.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RewardConfig {
+    #[serde(default = "default_typical_reduction_kwh")]
+    typical_reduction_kwh: f64,
+    /// The fraction of the price that reaches the household, e.g. 0.8 if the supplier keeps 20%.
+    #[serde(default = "default_pass_through")]
+    pass_through: f64,
+}
+
+impl RewardConfig {
+    /// The estimated reward in pounds for turning down by the typical amount at the price.
+    pub fn estimate(&self, price_gbp_per_mwh: f64) -> f64 {
+        price_gbp_per_mwh / 1000.0 * self.typical_reduction_kwh * self.pass_through
+    }
+
+    pub fn describe(&self, price_gbp_per_mwh: f64) -> String {
+        format!("Estimated reward: £{:.2} for saving {} kWh", self.estimate(price_gbp_per_mwh), self.typical_reduction_kwh)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reward::RewardConfig;
+
+    #[test]
+    fn test_estimate() {
+        let reward = RewardConfig {
+            typical_reduction_kwh: 2.0,
+            pass_through: 0.5,
+        };
+        assert_eq!(reward.estimate(3000.0), 3.0);
+        assert_eq!(reward.describe(3000.0), "Estimated reward: £3.00 for saving 2 kWh");
+    }
+}
@@ -1,5 +1,15 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
+use crate::feed::{LIVE_PACKAGE_2022, NEW_PACKAGE_2023, TEST_PACKAGE_2022};
+use crate::consumption::Turndown;
+use crate::lifecycle::EventLifecycle;
+use crate::quiet::QueuedMessage;
+use crate::reminder::ReminderState;
 use crate::resource::anticipation::DfsIndustryNotification;
+use crate::resource::digest::ResourceTimestamps;
+use crate::resource::requirement::ServiceRequirement;
+use crate::resource::utilisation::EventOutcome;
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct AllSavedData {
@@ -9,19 +19,69 @@ pub struct AllSavedData {
     pub live: SavedData,
     #[serde(default)]
     pub new_2023: SavedData,
+    #[serde(default)]
+    pub reminders: ReminderState,
+    /// Every datapackage that discovery has seen.
+    #[serde(default)]
+    pub known_packages: BTreeSet<String>,
+    /// Feeds that were discovered and are being watched, keyed by package name.
+    #[serde(default)]
+    pub discovered: BTreeMap<String, SavedData>,
+    /// The turn-down of every event that has been worked out.
+    #[serde(default)]
+    pub turndowns: Vec<Turndown>,
+    /// Messages held back during quiet hours, keyed by the profile whose router they are for, or "default".
+    #[serde(default)]
+    pub queued: BTreeMap<String, Vec<QueuedMessage>>,
+    /// When the last daily digest was sent.
+    #[serde(default)]
+    pub last_daily_digest: Option<NaiveDate>,
 }
 
 impl AllSavedData {
-    pub fn get_test(&self) -> &SavedData {
-        &self.test
+    /// The saved data for a datapackage. The built-in feeds keep their own fields so older state files still load.
+    pub fn get_feed(&self, package: &str) -> Option<&SavedData> {
+        match package {
+            LIVE_PACKAGE_2022 => Some(&self.live),
+            TEST_PACKAGE_2022 => Some(&self.test),
+            NEW_PACKAGE_2023 => Some(&self.new_2023),
+            _ => self.discovered.get(package),
+        }
+    }
+
+    pub fn set_feed(&mut self, package: &str, data: SavedData) {
+        match package {
+            LIVE_PACKAGE_2022 => self.live = data,
+            TEST_PACKAGE_2022 => self.test = data,
+            NEW_PACKAGE_2023 => self.new_2023 = data,
+            _ => {
+                self.discovered.insert(package.to_owned(), data);
+            }
+        }
+    }
+
+    pub fn get_discovered(&self) -> &BTreeMap<String, SavedData> {
+        &self.discovered
     }
 
-    pub fn get_live(&self) -> &SavedData {
-        &self.live
+    pub fn get_known_packages(&self) -> &BTreeSet<String> {
+        &self.known_packages
     }
 
-    pub fn get_new(&self) -> &SavedData {
-        &self.new_2023
+    pub fn get_turndowns(&self) -> &Vec<Turndown> {
+        &self.turndowns
+    }
+
+    pub fn get_reminders(&self) -> &ReminderState {
+        &self.reminders
+    }
+
+    pub fn get_last_daily_digest(&self) -> &Option<NaiveDate> {
+        &self.last_daily_digest
+    }
+
+    pub fn get_queued(&self) -> &BTreeMap<String, Vec<QueuedMessage>> {
+        &self.queued
     }
 }
 
@@ -29,10 +89,63 @@ impl AllSavedData {
 pub struct SavedData {
     #[serde(default)]
     pub industry_notification: Option<DfsIndustryNotification>,
+    #[serde(default)]
+    pub service_requirements: Vec<ServiceRequirement>,
+    #[serde(default)]
+    pub known_resources: HashMap<String, BTreeMap<String, String>>,
+    #[serde(default)]
+    pub resource_timestamps: ResourceTimestamps,
+    #[serde(default)]
+    pub lifecycle: EventLifecycle,
+    /// Only the dates, from before the number of rows was kept.
+    #[serde(default)]
+    pub utilisation_reported: BTreeSet<NaiveDate>,
+    #[serde(default)]
+    pub utilisation_rows: BTreeMap<NaiveDate, usize>,
+    /// Whether the utilisation report has been read, as it can be before it has any rows.
+    #[serde(default)]
+    pub utilisation_read: bool,
+    #[serde(default)]
+    pub history: Vec<EventOutcome>,
 }
 
 impl SavedData {
     pub fn get_industry_notification(&self) -> &Option<DfsIndustryNotification> {
         &self.industry_notification
     }
-}
\ No newline at end of file
+
+    pub fn get_service_requirements(&self) -> &Vec<ServiceRequirement> {
+        &self.service_requirements
+    }
+
+    pub fn get_known_resources(&self) -> &HashMap<String, BTreeMap<String, String>> {
+        &self.known_resources
+    }
+
+    pub fn get_resource_timestamps(&self) -> &ResourceTimestamps {
+        &self.resource_timestamps
+    }
+
+    pub fn get_lifecycle(&self) -> &EventLifecycle {
+        &self.lifecycle
+    }
+
+    /// How many rows each reported date had, 0 if it was saved before that was kept.
+    /// `None` if the report hadn't been read.
+    pub fn get_utilisation_reported(&self) -> Option<BTreeMap<NaiveDate, usize>> {
+        let mut reported: BTreeMap<NaiveDate, usize> = self.utilisation_reported.iter()
+            .map(|date| (*date, 0))
+            .collect();
+        reported.extend(self.utilisation_rows.iter().map(|(date, count)| (*date, *count)));
+        // Older saved state only shows it was read if something was reported.
+        if self.utilisation_read || !reported.is_empty() {
+            Some(reported)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_history(&self) -> &Vec<EventOutcome> {
+        &self.history
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::NaiveDateTime;
+use rnotifylib::message::{Level, Message};
+use rnotifylib::message::builder::MessageBuilder;
+use rnotifylib::message::component::Component;
+use rnotifylib::message::detail_builder::FormattedStringAppendable;
+use crate::feed::Feed;
+use crate::region::Region;
+use crate::resource::requirement::next_window;
+
+/// Listens for the signals that ask dfs_alert to stop.
+/// The handlers are registered straight away, so that a signal during a poll isn't missed.
+#[cfg(unix)]
+pub struct Shutdown {
+    terminate: tokio::signal::unix::Signal,
+    interrupt: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl Shutdown {
+    pub fn listen() -> std::io::Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+        Ok(Self {
+            terminate: signal(SignalKind::terminate())?,
+            interrupt: signal(SignalKind::interrupt())?,
+        })
+    }
+
+    /// Waits for a signal, returning its name.
+    pub async fn recv(&mut self) -> &'static str {
+        tokio::select! {
+            _ = self.terminate.recv() => "SIGTERM",
+            _ = self.interrupt.recv() => "SIGINT",
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub struct Shutdown;
+
+#[cfg(not(unix))]
+impl Shutdown {
+    pub fn listen() -> std::io::Result<Self> {
+        Ok(Self)
+    }
+
+    pub async fn recv(&mut self) -> &'static str {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl-C"
+    }
+}
+
+/// Says what dfs_alert is watching and the last thing it knew about each feed, with the next event in the region.
+pub fn create_started_message(feeds: &mut [Feed], region: &Option<Region>, state_loaded: bool, now: &NaiveDateTime) -> Message {
+    let today = now.date();
+    let mut lines = vec![];
+    for feed in feeds.iter_mut() {
+        let name = feed.get_name().to_owned();
+        let resources = feed.get_resources_mut();
+        let last = resources.get_anticipated().get_last_data().as_ref()
+            .map(|notification| notification.summary(&today))
+            .unwrap_or_else(|| "nothing yet".to_owned());
+        let next = next_window(resources.get_requirements().in_region(region).get_windows(), now)
+            .map(|(start, end)| format!(", next event {} - {}", start.format("%d/%m %H:%M"), end.format("%H:%M")))
+            .unwrap_or_default();
+        lines.push(format!("{}: {}{}\n", name, last, next));
+    }
+    let mut message_builder = MessageBuilder::new();
+    message_builder
+        .level(Level::Info)
+        .title(format!("dfs_alert started, watching {} feeds", feeds.len()))
+        .body(|body| {
+            body.raw(lines.concat());
+            body.text_block(|block| {
+                if !state_loaded {
+                    block.append_plain("No previous state was loaded\n");
+                }
+            });
+            body.section("Last known state", |builder| {
+                for line in &lines {
+                    builder.append_plain(line);
+                }
+            });
+        })
+        .component(Component::from("dfs_alert/service"))
+        .author("dfs_alert");
+    message_builder.build()
+}
+
+pub fn create_stopping_message(signal: &str) -> Message {
+    let mut message_builder = MessageBuilder::new();
+    message_builder
+        .level(Level::Info)
+        .title("dfs_alert stopping")
+        .body(|body| {
+            body.raw(format!("Received {}", signal));
+            body.text_block(|block| {
+                block.append_plain(format!("Received {}, state has been saved\n", signal));
+            });
+        })
+        .component(Component::from("dfs_alert/service"))
+        .author("dfs_alert");
+    message_builder.build()
+}
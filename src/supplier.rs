@@ -0,0 +1,134 @@
+use serde::Deserialize;
+
+/// Suppliers ESO are known to have named, with the ways they have written them.
+const BUILT_IN: &[(&str, &[&str])] = &[
+    ("octopus", &["Octopus Energy Limited", "Octopus Energy", "Octopus"]),
+    ("british_gas", &["British Gas Trading Limited", "British Gas", "Centrica"]),
+    ("edf", &["EDF Energy Customers Limited", "EDF Energy", "EDF"]),
+    ("eon", &["E.ON Next Energy Limited", "E.ON Next", "E.ON Energy Solutions Limited", "E.ON"]),
+    ("ovo", &["OVO Electricity Limited", "OVO Energy", "OVO"]),
+    ("scottish_power", &["Scottish Power Energy Retail Limited", "ScottishPower"]),
+    ("so_energy", &["So Energy Trading Limited", "So Energy"]),
+    ("utility_warehouse", &["Telecom Plus PLC", "Utility Warehouse"]),
+];
+
+/// Endings that ESO add or leave off at random.
+const SUFFIXES: &[&str] = &["limited", "ltd", "plc", "trading"];
+
+/// How many characters can differ for a name to still match an alias.
+const MAX_TYPOS: usize = 2;
+
+/// How long an alias has to be for each typo it allows, as short names are too easy to confuse, e.g. "ovo" and "edf".
+const CHARS_PER_TYPO: usize = 6;
+
+/// Over 6 characters allow one typo, over 12 allow two.
+fn allowed_typos(alias: &str) -> usize {
+    (alias.len().saturating_sub(1) / CHARS_PER_TYPO).min(MAX_TYPOS)
+}
+
+/// Extra suppliers or aliases, configured with `[[supplier]]` tables in `dfs_alert.toml`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SupplierConfig {
+    id: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
+/// Ignores case, spaces, punctuation and company suffixes, so "OCTOPUS ENERGY LTD" is "octopusenergy".
+fn normalise(name: &str) -> String {
+    let mut name: String = name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    while let Some(suffix) = SUFFIXES.iter().find(|suffix| name.len() > suffix.len() && name.ends_with(*suffix)) {
+        name.truncate(name.len() - suffix.len());
+    }
+    name
+}
+
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Works out which supplier a name refers to, however it has been written.
+pub struct SupplierRegistry {
+    /// Canonical id, with normalised aliases (including the id itself).
+    suppliers: Vec<(String, Vec<String>)>,
+}
+
+impl SupplierRegistry {
+    pub fn new(extra: &[SupplierConfig]) -> Self {
+        let mut suppliers: Vec<(String, Vec<String>)> = BUILT_IN.iter()
+            .map(|(id, aliases)| (id.to_string(), aliases.iter().map(|alias| normalise(alias)).collect()))
+            .collect();
+        for config in extra {
+            let aliases = config.aliases.iter().map(|alias| normalise(alias));
+            match suppliers.iter_mut().find(|(id, _)| id == &config.id) {
+                Some((_, existing)) => existing.extend(aliases),
+                None => suppliers.push((config.id.clone(), aliases.collect())),
+            }
+        }
+        for (id, aliases) in &mut suppliers {
+            aliases.push(normalise(id));
+        }
+        Self {
+            suppliers,
+        }
+    }
+
+    /// The canonical id of the supplier, matching exactly if possible, otherwise allowing a couple of typos.
+    /// Returns `None` if the name doesn't match any known alias, or is as close to another supplier's.
+    pub fn identify(&self, name: &str) -> Option<&str> {
+        let name = normalise(name);
+        if name.is_empty() {
+            return None;
+        }
+        let exact = self.suppliers.iter()
+            .find(|(_, aliases)| aliases.contains(&name));
+        if let Some((id, _)) = exact {
+            return Some(id);
+        }
+        let mut closest: Vec<(&str, usize)> = self.suppliers.iter()
+            .filter_map(|(id, aliases)| aliases.iter()
+                .map(|alias| (alias, distance(alias, &name)))
+                .filter(|(alias, distance)| *distance <= allowed_typos(alias))
+                .map(|(_, distance)| distance)
+                .min()
+                .map(|distance| (id.as_str(), distance)))
+            .collect();
+        closest.sort_by_key(|(_, distance)| *distance);
+        match closest.as_slice() {
+            [(id, _)] => Some(id),
+            [(id, best), (_, next), ..] if best < next => Some(id),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::supplier::SupplierRegistry;
+
+    #[test]
+    fn test_identify() {
+        let registry = SupplierRegistry::new(&[]);
+        for name in ["OctopusEnergyLimited", "Octopus Energy Limited", "OCTOPUS ENERGY LTD", "octopus-energy", "Octopus Enrgy Ltd"] {
+            assert_eq!(registry.identify(name), Some("octopus"), "{}", name);
+        }
+        assert_eq!(registry.identify("E.ON Next"), Some("eon"));
+        // Two letters from "EDF Energy", which is too many for a name that short.
+        assert_eq!(registry.identify("E.ON Energy Ltd"), None);
+        assert_eq!(registry.identify("Some New Supplier Ltd"), None);
+        assert_eq!(registry.identify("Limited"), None);
+    }
+}
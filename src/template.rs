@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::error::Error;
+use handlebars::Handlebars;
+use rnotifylib::message::{Level, Message};
+use rnotifylib::message::builder::MessageBuilder;
+use rnotifylib::message::component::Component;
+use rnotifylib::message::detail_builder::FormattedStringAppendable;
+use serde::Deserialize;
+use crate::webhook::{WebhookEventType, WebhookPayload};
+
+/// A user supplied message for one type of event, configured with e.g. a `[templates.published]` table.
+/// Templates use handlebars syntax and can use any field of the webhook payload, e.g. `{{event_date}}`.
+/// The same notes as the built-in messages, e.g. the expected reward, are added after the body.
+#[derive(Deserialize, Debug, Clone)]
+pub struct MessageTemplate {
+    title: String,
+    body: String,
+}
+
+/// Renders messages from templates, for the event types that have one.
+pub struct Templates {
+    registry: Handlebars<'static>,
+}
+
+impl Templates {
+    pub fn new(templates: &HashMap<String, MessageTemplate>) -> Result<Self, Box<dyn Error>> {
+        let mut registry = Handlebars::new();
+        // These are plain text messages, not html.
+        registry.register_escape_fn(handlebars::no_escape);
+        for (event_type, template) in templates {
+            if !WebhookEventType::ALL.iter().any(|known| known.get_name() == event_type) {
+                let known: Vec<&str> = WebhookEventType::ALL.iter().map(WebhookEventType::get_name).collect();
+                return Err(format!("Unknown event type '{}' in [templates], expected one of {}", event_type, known.join(", ")).into());
+            }
+            registry.register_template_string(&title_name(event_type), &template.title)?;
+            registry.register_template_string(&body_name(event_type), &template.body)?;
+        }
+        Ok(Self {
+            registry,
+        })
+    }
+
+    /// Renders the title and body from the template for this type of event.
+    /// Returns `None` if there isn't a template or it failed.
+    fn render(&self, payload: &WebhookPayload) -> Option<(String, String)> {
+        let event_type = payload.get_event_type_name();
+        if !self.registry.has_template(&title_name(event_type)) {
+            return None;
+        }
+        let rendered = self.registry.render(&title_name(event_type), payload)
+            .and_then(|title| Ok((title, self.registry.render(&body_name(event_type), payload)?)));
+        match rendered {
+            Ok(rendered) => Some(rendered),
+            Err(err) => {
+                eprintln!("Failed to render {} template, using default: {}", event_type, err);
+                None
+            }
+        }
+    }
+
+    /// Creates the message from the template for this type of event, with the `notes` after the body as in the built-in messages.
+    /// Returns `None` to fall back to the built-in message if there isn't a template or it failed.
+    pub fn create_message(&self, payload: &WebhookPayload, notes: &[String], level: Level, component: &str) -> Option<Message> {
+        let (title, body) = self.render(payload)?;
+
+        let mut message_builder = MessageBuilder::new();
+        message_builder
+            .level(level)
+            .title(title)
+            .body(|builder| {
+                builder.raw(body.clone());
+                builder.text_block(|block| {
+                    block.append_plain(body);
+                    for note in notes {
+                        block.append_plain(format!("\n{}", note));
+                    }
+                });
+            })
+            .component(Component::from(component))
+            .author("dfs_alert");
+        Some(message_builder.build())
+    }
+}
+
+fn title_name(event_type: &str) -> String {
+    format!("{}.title", event_type)
+}
+
+fn body_name(event_type: &str) -> String {
+    format!("{}.body", event_type)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use rnotifylib::message::Level;
+    use crate::template::{MessageTemplate, Templates};
+    use crate::webhook::WebhookPayload;
+
+    #[test]
+    fn test_fallback() {
+        let mut templates = HashMap::new();
+        templates.insert("published".to_owned(), MessageTemplate {
+            title: "DFS on {{event_date}}".to_owned(),
+            body: "{{description}}".to_owned(),
+        });
+        let templates = Templates::new(&templates).expect("Templates should be valid");
+
+        // The sample payload is a test event, which has no template.
+        let message = templates.create_message(&WebhookPayload::sample(), &[], Level::Info, "dfs/test");
+        assert!(message.is_none());
+    }
+
+    #[test]
+    fn test_render() {
+        let mut templates = HashMap::new();
+        templates.insert("test".to_owned(), MessageTemplate {
+            title: "Test from {{feed}}".to_owned(),
+            body: "{{start_time}} until {{end_time}}".to_owned(),
+        });
+        let templates = Templates::new(&templates).expect("Templates should be valid");
+
+        let (title, body) = templates.render(&WebhookPayload::sample()).expect("Should render");
+        assert_eq!(title, "Test from webhook test");
+        assert_eq!(body, "17:30:00 until 18:30:00");
+    }
+
+    #[test]
+    fn test_unknown_event_type() {
+        let mut templates = HashMap::new();
+        templates.insert("publised".to_owned(), MessageTemplate {
+            title: "DFS".to_owned(),
+            body: "DFS".to_owned(),
+        });
+        assert!(Templates::new(&templates).is_err());
+    }
+}
@@ -0,0 +1,312 @@
+use std::error::Error;
+use std::time::Duration;
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use crate::lifecycle::{EventStage, Transition};
+use crate::NewPossibleEvent;
+use crate::resource::anticipation::DfsIndustryNotification;
+use crate::resource::status::Tagging;
+
+const SIGNATURE_HEADER: &str = "X-DFS-Alert-Signature";
+
+fn default_retries() -> u32 {
+    3
+}
+
+/// An outbound webhook, configured with a `[[webhook]]` table in `dfs_alert.toml`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct WebhookConfig {
+    url: String,
+    /// If set, the body is signed with HMAC-SHA256 and sent in the `X-DFS-Alert-Signature` header.
+    #[serde(default)]
+    secret: Option<String>,
+    /// How many times to retry a failed delivery before giving up.
+    #[serde(default = "default_retries")]
+    retries: u32,
+    /// Whether to also send events from test feeds.
+    #[serde(default)]
+    include_test: bool,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventType {
+    Anticipated,
+    Published,
+    Cancelled,
+    Test,
+    SupplierConfirmed,
+    Unrecognised,
+}
+
+impl WebhookEventType {
+    pub const ALL: [WebhookEventType; 6] = [
+        WebhookEventType::Anticipated,
+        WebhookEventType::Published,
+        WebhookEventType::Cancelled,
+        WebhookEventType::Test,
+        WebhookEventType::SupplierConfirmed,
+        WebhookEventType::Unrecognised,
+    ];
+
+    /// The same name as it is serialized with.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            WebhookEventType::Anticipated => "anticipated",
+            WebhookEventType::Published => "published",
+            WebhookEventType::Cancelled => "cancelled",
+            WebhookEventType::Test => "test",
+            WebhookEventType::SupplierConfirmed => "supplier_confirmed",
+            WebhookEventType::Unrecognised => "unrecognised",
+        }
+    }
+}
+
+/// The JSON body POSTed to webhooks, also used as the context for message templates.
+/// Fields are only ever added to this, so that automations don't break.
+#[derive(Serialize, Debug, Clone)]
+pub struct WebhookPayload {
+    event_type: WebhookEventType,
+    feed: String,
+    issued_at: Option<NaiveDateTime>,
+    event_date: Option<NaiveDate>,
+    start_time: Option<NaiveTime>,
+    end_time: Option<NaiveTime>,
+    description: Option<String>,
+    /// A human-friendly summary, e.g. "DFS event confirmed tomorrow (Tue 24 Jan)".
+    summary: Option<String>,
+    tagging: Option<Tagging>,
+    follow_up_time: Option<NaiveTime>,
+    /// "accepted", "rejected" or "no_bid" for the profile's supplier, or the default one for webhooks.
+    /// Only filled in after the event, as that is when the utilisation report has the results,
+    /// so it is null for the notifications before an event. "confirmed" is for when suppliers say they are taking part,
+    /// which no feed gives yet.
+    supplier_status: Option<String>,
+    price_gbp_per_mwh: Option<f64>,
+    /// Where the event for the date has got to, and where it was before this.
+    stage: Option<EventStage>,
+    previous_stage: Option<EventStage>,
+    test: bool,
+}
+
+impl WebhookPayload {
+    /// `test` is whether it came from a test feed, as well as test notifications.
+    pub fn from_event(feed: &str, event: &NewPossibleEvent, test: bool) -> Self {
+        let (event_type, notification) = match event {
+            NewPossibleEvent::Expected(n) => (WebhookEventType::Anticipated, Some(n)),
+            NewPossibleEvent::Confirmed(n) => (WebhookEventType::Published, Some(n)),
+            NewPossibleEvent::Cancelled(n) => (WebhookEventType::Cancelled, Some(n)),
+            NewPossibleEvent::OurSupplierConfirmed => (WebhookEventType::SupplierConfirmed, None),
+            NewPossibleEvent::Test(n) => (WebhookEventType::Test, Some(n)),
+            NewPossibleEvent::Unrecognised(n) => (WebhookEventType::Unrecognised, Some(n)),
+        };
+        Self::new(event_type, feed, notification, test)
+    }
+
+    fn new(event_type: WebhookEventType, feed: &str, notification: Option<&DfsIndustryNotification>, test: bool) -> Self {
+        let details = notification.map(|n| n.get_status_details());
+        Self {
+            event_type,
+            feed: feed.to_owned(),
+            issued_at: notification.map(|n| *n.get_when()),
+            event_date: notification.and_then(|n| n.get_event_date()),
+            start_time: None,
+            end_time: None,
+            description: notification.map(|n| n.get_description().to_owned()),
+            summary: notification.map(|n| n.summary(&Local::now().date_naive())),
+            tagging: details.as_ref().and_then(|d| *d.get_tagging()),
+            follow_up_time: details.as_ref().and_then(|d| *d.get_follow_up()),
+            supplier_status: Some("confirmed".to_owned()).filter(|_| event_type == WebhookEventType::SupplierConfirmed),
+            price_gbp_per_mwh: None,
+            stage: None,
+            previous_stage: None,
+            test: test || event_type == WebhookEventType::Test,
+        }
+    }
+
+    /// Fills in the event times, once they have been published in the service requirements.
+    pub fn with_window(mut self, window: Option<(NaiveDateTime, NaiveDateTime)>) -> Self {
+        if let Some((start, end)) = window {
+            self.start_time = Some(start.time());
+            self.end_time = Some(end.time());
+        }
+        self
+    }
+
+    /// Fills in the guaranteed acceptance price, if the service requirements give one.
+    pub fn with_price(mut self, price: Option<f64>) -> Self {
+        self.price_gbp_per_mwh = price;
+        self
+    }
+
+    /// Fills in how our supplier is taking part, if it is known.
+    pub fn with_supplier_status(mut self, status: Option<&str>) -> Self {
+        if let Some(status) = status {
+            self.supplier_status = Some(status.to_owned());
+        }
+        self
+    }
+
+    pub fn get_price(&self) -> &Option<f64> {
+        &self.price_gbp_per_mwh
+    }
+
+    pub fn with_transition(mut self, transition: Option<&Transition>) -> Self {
+        if let Some(transition) = transition {
+            self.stage = Some(transition.get_to());
+            self.previous_stage = Some(transition.get_from());
+        }
+        self
+    }
+
+    pub fn get_event_type_name(&self) -> &'static str {
+        self.event_type.get_name()
+    }
+
+    pub fn get_event_date(&self) -> &Option<NaiveDate> {
+        &self.event_date
+    }
+
+    /// A made up payload for checking that webhooks are set up correctly.
+    pub fn sample() -> Self {
+        let now = Local::now().naive_local();
+        Self {
+            event_type: WebhookEventType::Test,
+            feed: "webhook test".to_owned(),
+            issued_at: Some(now),
+            event_date: Some(now.date()),
+            start_time: NaiveTime::from_hms_opt(17, 30, 0),
+            end_time: NaiveTime::from_hms_opt(18, 30, 0),
+            description: Some("This is a test webhook from dfs_alert.".to_owned()),
+            summary: Some("Test DFS event today".to_owned()),
+            tagging: Some(Tagging::SystemTagged),
+            follow_up_time: None,
+            supplier_status: Some("confirmed".to_owned()),
+            price_gbp_per_mwh: None,
+            stage: None,
+            previous_stage: None,
+            test: true,
+        }
+    }
+}
+
+pub struct Webhooks {
+    client: Client,
+    configs: Vec<WebhookConfig>,
+}
+
+impl Webhooks {
+    pub fn new(configs: Vec<WebhookConfig>) -> Self {
+        Self {
+            client: Client::new(),
+            configs,
+        }
+    }
+
+    /// Sends the payload to every configured webhook, returning how many accepted it.
+    pub async fn send(&self, payload: &WebhookPayload) -> usize {
+        let configs: Vec<&WebhookConfig> = self.configs.iter()
+            .filter(|config| !payload.test || config.include_test)
+            .collect();
+        self.send_to_all(&configs, payload).await
+    }
+
+    /// Sends a sample payload to every configured webhook, regardless of `include_test`.
+    pub async fn send_sample(&self) -> usize {
+        let configs: Vec<&WebhookConfig> = self.configs.iter().collect();
+        self.send_to_all(&configs, &WebhookPayload::sample()).await
+    }
+
+    async fn send_to_all(&self, configs: &[&WebhookConfig], payload: &WebhookPayload) -> usize {
+        let body = match serde_json::to_vec(payload) {
+            Ok(body) => body,
+            Err(err) => {
+                eprintln!("Failed to serialize webhook payload: {}", err);
+                return 0;
+            }
+        };
+        let mut sent = 0;
+        for config in configs {
+            match self.send_to(config, &body).await {
+                Ok(_) => sent += 1,
+                Err(err) => eprintln!("Failed to send webhook to {}: {}", config.url, err),
+            }
+        }
+        sent
+    }
+
+    async fn send_to(&self, config: &WebhookConfig, body: &[u8]) -> Result<(), Box<dyn Error>> {
+        let mut attempt = 0;
+        loop {
+            match self.try_send(config, body).await {
+                Ok(_) => return Ok(()),
+                Err(err) if attempt < config.retries => {
+                    let backoff = Duration::from_secs(2u64.pow(attempt));
+                    eprintln!("Webhook to {} failed ({}), retrying in {:?}", config.url, err, backoff);
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    async fn try_send(&self, config: &WebhookConfig, body: &[u8]) -> Result<(), Box<dyn Error>> {
+        let mut request = self.client.post(&config.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_vec());
+        if let Some(secret) = &config.secret {
+            request = request.header(SIGNATURE_HEADER, format!("sha256={}", sign(secret, body)));
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use crate::NewPossibleEvent;
+    use crate::resource::anticipation::{DfsIndustryNotification, IndustryNotificationType};
+    use crate::webhook::{sign, WebhookPayload};
+
+    #[test]
+    fn test_payload_fields() {
+        // Automations rely on these names, so they must not change.
+        let value = serde_json::to_value(WebhookPayload::sample()).unwrap();
+        let mut fields: Vec<&str> = value.as_object().unwrap().keys().map(String::as_str).collect();
+        fields.sort();
+        assert_eq!(fields, vec!["description", "end_time", "event_date", "event_type", "feed", "follow_up_time", "issued_at",
+                                "previous_stage", "price_gbp_per_mwh", "stage", "start_time", "summary", "supplier_status",
+                                "tagging", "test"]);
+        assert_eq!(value["event_type"], "test");
+        assert_eq!(value["supplier_status"], "confirmed");
+        assert_eq!(value["start_time"], "17:30:00");
+    }
+
+    #[test]
+    fn test_unrecognised_on_test_feed() {
+        let when = NaiveDate::from_ymd_opt(2023, 12, 14).unwrap().and_hms_opt(9, 0, 0).unwrap();
+        let notification = DfsIndustryNotification::new(IndustryNotificationType::RequirementPublished, when, "Something new".to_owned(), None);
+        let event = NewPossibleEvent::Unrecognised(notification).into_test();
+        let value = serde_json::to_value(WebhookPayload::from_event("test", &event, true)).unwrap();
+        assert_eq!(value["event_type"], "unrecognised");
+        assert_eq!(value["test"], true);
+    }
+
+    #[test]
+    fn test_sign() {
+        let signature = sign("key", b"The quick brown fox jumps over the lazy dog");
+        assert_eq!(signature, "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8");
+    }
+}